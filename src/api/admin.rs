@@ -0,0 +1,244 @@
+use axum::{extract::State, Json};
+use futures_util::{stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::api::extract::JsonBody;
+use crate::api::health::AppState;
+use crate::config::Settings;
+use crate::error::AppError;
+use crate::models::EmbeddingOptions;
+use crate::metrics::{HistogramSnapshot, Metrics};
+use crate::services::tokenizer_service::TokenizerService;
+
+/// Upper bounds keeping a load test from turning into a denial of service
+const MAX_LOADTEST_REQUESTS: usize = 10_000;
+const MAX_LOADTEST_CONCURRENCY: usize = 256;
+const MAX_LOADTEST_BATCH_SIZE: usize = 256;
+
+#[derive(Debug, Deserialize)]
+pub struct LoadTestRequest {
+    /// Number of synthetic embedding requests to send
+    #[serde(default = "default_requests")]
+    pub requests: usize,
+    /// Number of requests in flight at once
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Inputs per synthetic request
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    #[serde(default = "default_text")]
+    pub text: String,
+    #[serde(default = "default_task")]
+    pub task: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoadTestResponse {
+    pub requests: usize,
+    pub concurrency: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub total_ms: f64,
+    pub requests_per_sec: f64,
+    pub latency_ms: LatencyPercentiles,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LatencyPercentiles {
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsResponse {
+    pub error_rate: f64,
+    pub error_rate_window_requests: u64,
+    pub sequence_length: SequenceLengthMetrics,
+}
+
+/// Padded sequence length of each batch sent to Triton, per model
+#[derive(Debug, Serialize)]
+pub struct SequenceLengthMetrics {
+    pub embedding: HistogramSnapshot,
+    pub reranker: HistogramSnapshot,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FlushCacheResponse {
+    /// Entries cleared from the embedding service caches
+    pub embedding: usize,
+    /// Entries cleared from the reranking service caches
+    pub reranking: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReloadTokenizersResponse {
+    /// Vocabulary size of the reloaded embedding tokenizer, added tokens included
+    pub embedding_vocab_size: usize,
+    /// Vocabulary size of the reloaded reranker tokenizer, added tokens included
+    pub reranker_vocab_size: usize,
+    /// Cached embeddings dropped because they came from the old tokenizer
+    pub cache_entries_cleared: usize,
+}
+
+fn default_requests() -> usize {
+    100
+}
+
+fn default_concurrency() -> usize {
+    8
+}
+
+fn default_batch_size() -> usize {
+    1
+}
+
+fn default_text() -> String {
+    "Machine learning là một nhánh của trí tuệ nhân tạo.".to_string()
+}
+
+fn default_task() -> String {
+    "retrieval.query".to_string()
+}
+
+/// Sends synthetic embedding requests through the service (and Triton) and
+/// reports latency percentiles. Only routed when admin routes are enabled.
+pub async fn run_loadtest(
+    State(state): State<Arc<AppState>>,
+    JsonBody(request): JsonBody<LoadTestRequest>,
+) -> Result<Json<LoadTestResponse>, AppError> {
+    if request.requests == 0 || request.requests > MAX_LOADTEST_REQUESTS {
+        return Err(AppError::Validation(format!(
+            "requests must be between 1 and {}",
+            MAX_LOADTEST_REQUESTS
+        )));
+    }
+    if request.concurrency == 0 || request.concurrency > MAX_LOADTEST_CONCURRENCY {
+        return Err(AppError::Validation(format!(
+            "concurrency must be between 1 and {}",
+            MAX_LOADTEST_CONCURRENCY
+        )));
+    }
+    let max_batch_size = Settings::get()
+        .max_inputs_per_request
+        .map_or(MAX_LOADTEST_BATCH_SIZE, |limit| limit.min(MAX_LOADTEST_BATCH_SIZE));
+    if request.batch_size == 0 || request.batch_size > max_batch_size {
+        return Err(AppError::Validation(format!(
+            "batch_size must be between 1 and {}",
+            max_batch_size
+        )));
+    }
+
+    info!(
+        "Starting load test: {} requests, concurrency {}, batch size {}",
+        request.requests, request.concurrency, request.batch_size
+    );
+
+    let started = Instant::now();
+
+    // Each text is made distinct and the cache is bypassed so every request reaches Triton
+    let results: Vec<(Duration, bool)> = stream::iter(0..request.requests)
+        .map(|request_index| {
+            let service = state.embedding_service.clone();
+            let texts: Vec<String> = (0..request.batch_size)
+                .map(|item_index| format!("{} {}-{}", request.text, request_index, item_index))
+                .collect();
+            let task = request.task.clone();
+            async move {
+                let sent = Instant::now();
+                let options = EmbeddingOptions { no_cache: true, ..Default::default() };
+                let ok = service.create_embeddings(texts, &task, None, options).await.is_ok();
+                (sent.elapsed(), ok)
+            }
+        })
+        .buffer_unordered(request.concurrency)
+        .collect()
+        .await;
+
+    let total = started.elapsed();
+    let succeeded = results.iter().filter(|(_, ok)| *ok).count();
+    let mut latencies: Vec<f64> = results.iter().map(|(d, _)| d.as_secs_f64() * 1000.0).collect();
+    latencies.sort_by(f64::total_cmp);
+
+    let response = LoadTestResponse {
+        requests: request.requests,
+        concurrency: request.concurrency,
+        succeeded,
+        failed: request.requests - succeeded,
+        total_ms: total.as_secs_f64() * 1000.0,
+        requests_per_sec: request.requests as f64 / total.as_secs_f64(),
+        latency_ms: LatencyPercentiles {
+            mean: latencies.iter().sum::<f64>() / latencies.len() as f64,
+            p50: percentile(&latencies, 0.50),
+            p90: percentile(&latencies, 0.90),
+            p99: percentile(&latencies, 0.99),
+            max: latencies.last().copied().unwrap_or(0.0),
+        },
+    };
+
+    info!(
+        "Load test finished: {}/{} succeeded, p50 {:.1}ms, p99 {:.1}ms",
+        succeeded, request.requests, response.latency_ms.p50, response.latency_ms.p99
+    );
+    Ok(Json(response))
+}
+
+/// Nearest-rank percentile of ascending `sorted` values.
+fn percentile(sorted: &[f64], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let rank = (quantile * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Snapshot of the in-process metrics. Only routed when admin routes are enabled.
+pub async fn get_metrics() -> Json<MetricsResponse> {
+    let metrics = Metrics::get();
+    let (error_rate, requests) = metrics.recent_error_rate(Settings::get().error_rate_window_secs);
+
+    Json(MetricsResponse {
+        error_rate,
+        error_rate_window_requests: requests,
+        sequence_length: SequenceLengthMetrics {
+            embedding: metrics.embedding_sequence_length.snapshot(),
+            reranker: metrics.reranker_sequence_length.snapshot(),
+        },
+    })
+}
+
+/// Clears every in-memory cache so the next requests refetch from Triton.
+/// Only routed when admin routes are enabled.
+pub async fn flush_cache(State(state): State<Arc<AppState>>) -> Json<FlushCacheResponse> {
+    let embedding = state.embedding_service.clear_caches();
+    let reranking = state.reranking_service.clear_caches();
+
+    info!("Flushed caches: {} embedding entries, {} reranking entries", embedding, reranking);
+    Json(FlushCacheResponse {
+        embedding,
+        reranking,
+        total: embedding + reranking,
+    })
+}
+
+/// Reloads both tokenizers from the configured files without a restart, e.g.
+/// after fixing a bad `tokenizer.json`. Only routed when admin routes are enabled.
+pub async fn reload_tokenizers(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ReloadTokenizersResponse>, AppError> {
+    let sizes = TokenizerService::reload_tokenizers().await?;
+    let cache_entries_cleared = state.embedding_service.clear_embedding_cache();
+
+    Ok(Json(ReloadTokenizersResponse {
+        embedding_vocab_size: sizes.embedding,
+        reranker_vocab_size: sizes.reranker,
+        cache_entries_cleared,
+    }))
+}
@@ -0,0 +1,57 @@
+use arrow_array::{builder::{FixedSizeListBuilder, Float32Builder}, ArrayRef, RecordBatch, UInt32Array};
+use arrow_ipc::writer::StreamWriter;
+use arrow_schema::{DataType, Field, Schema};
+use std::sync::Arc;
+
+use crate::error::AppError;
+use crate::models::EmbeddingModel;
+
+/// Media type for Apache Arrow IPC stream responses
+pub const ARROW_STREAM_MEDIA_TYPE: &str = "application/vnd.apache.arrow.stream";
+
+/// Serializes embeddings as a single-batch Arrow IPC stream with an
+/// `index: uint32` column and an `embedding: fixed_size_list<float32>` column.
+pub fn embeddings_to_arrow(embeddings: &[EmbeddingModel]) -> Result<Vec<u8>, AppError> {
+    let dim = embeddings.first().map(|e| e.vector.len()).unwrap_or(0);
+
+    let mut vectors = FixedSizeListBuilder::with_capacity(
+        Float32Builder::with_capacity(embeddings.len() * dim),
+        dim as i32,
+        embeddings.len(),
+    );
+    for embedding in embeddings {
+        if embedding.vector.len() != dim {
+            return Err(AppError::Internal(format!(
+                "Embedding {} has dimension {}, expected {}",
+                embedding.index, embedding.vector.len(), dim
+            )));
+        }
+        vectors.values().append_slice(&embedding.vector);
+        vectors.append(true);
+    }
+
+    let indices = UInt32Array::from_iter_values(embeddings.iter().map(|e| e.index as u32));
+
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("index", DataType::UInt32, false),
+        Field::new(
+            "embedding",
+            DataType::FixedSizeList(Arc::new(Field::new("item", DataType::Float32, true)), dim as i32),
+            false,
+        ),
+    ]));
+
+    let columns: Vec<ArrayRef> = vec![Arc::new(indices), Arc::new(vectors.finish())];
+    let batch = RecordBatch::try_new(schema.clone(), columns)
+        .map_err(|e| AppError::Internal(format!("Failed to build Arrow record batch: {}", e)))?;
+
+    let mut buffer = Vec::new();
+    let mut writer = StreamWriter::try_new(&mut buffer, &schema)
+        .map_err(|e| AppError::Internal(format!("Failed to create Arrow writer: {}", e)))?;
+    writer.write(&batch)
+        .and_then(|_| writer.finish())
+        .map_err(|e| AppError::Internal(format!("Failed to write Arrow stream: {}", e)))?;
+    drop(writer);
+
+    Ok(buffer)
+}
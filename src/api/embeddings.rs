@@ -1,16 +1,29 @@
 use axum::{
     extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
     Json,
 };
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::info;
 
+use crate::api::arrow::{embeddings_to_arrow, ARROW_STREAM_MEDIA_TYPE};
+use crate::api::format::{negotiate, ResponseFormat};
+use crate::api::extract::{check_batch_items, request_timeout, JsonBody};
+use crate::api::headers::{wants_no_cache, with_model_version, with_no_store};
 use crate::api::health::AppState;
+use crate::config::Settings;
 use crate::error::AppError;
+use crate::metrics::Metrics;
 use crate::models::{
-    EmbeddingRequest, EmbeddingResponse, EmbeddingData, EmbeddingUsage,
+    EmbeddingModel, EmbeddingOutput, EmbeddingRequest, EmbeddingResponse, EmbeddingData, EmbeddingUsage, EncodingFormat,
+    EmbeddingDebug, EmbeddingInputs, EmbeddingOptions, EmbeddingRequestParams, EmbeddingPairRequest, EmbeddingPairResponse,
 };
 
+const QUERY_TASK: &str = "retrieval.query";
+const PASSAGE_TASK: &str = "retrieval.passage";
+
 #[utoipa::path(
     post,
     path = "/v1/embeddings",
@@ -26,40 +39,298 @@ use crate::models::{
         })
     ),
     responses(
-        (status = 200, description = "Successfully generated embeddings", body = EmbeddingResponse),
-        (status = 400, description = "Bad request - invalid task type or input"),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "Successfully generated embeddings. With `encoding_format: \"base64\"` each embedding is a base64 string of little-endian f32 bytes.", body = EmbeddingResponse),
+        (status = 200, description = "Embeddings as an Arrow IPC stream (index: uint32, embedding: fixed_size_list<float32>) when requested via `Accept: application/vnd.apache.arrow.stream` without an `encoding_format` field", content_type = "application/vnd.apache.arrow.stream"),
+        (status = 400, description = "Bad request - invalid task type, encoding format, input or unknown model"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Embedding model not ready or Triton unavailable")
     )
 )]
 pub async fn create_embeddings(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<EmbeddingRequest>,
-) -> Result<Json<EmbeddingResponse>, AppError> {
-    let texts = request.input.to_vec();
+    headers: HeaderMap,
+    JsonBody(request): JsonBody<EmbeddingRequest>,
+) -> Result<Response, AppError> {
+    Metrics::get()
+        .embedding_requests
+        .track(embed(state, headers, request))
+        .await
+}
+
+async fn embed(
+    state: Arc<AppState>,
+    headers: HeaderMap,
+    request: EmbeddingRequest,
+) -> Result<Response, AppError> {
+    if Settings::get().precheck_readiness {
+        state.embedding_service.ensure_ready().await?;
+    }
+
+    let response_format = negotiate(&headers, request.encoding_format.as_deref())?;
+    let model = state.embedding_service.resolve_model(request.model.as_deref())?.to_string();
+    let no_cache = wants_no_cache(&headers, request.no_cache);
+    let single_input = request.input.is_single();
+    let inputs = request.input.into_inputs();
+    let input_count = inputs.len();
+    if inputs.is_empty() {
+        return Err(AppError::Validation("input array cannot be empty".to_string()));
+    }
+    check_batch_items("input", input_count)?;
+    let normalized = request.normalized.unwrap_or(true);
+    let options = EmbeddingOptions {
+        normalize: normalized,
+        isolate_errors: request.isolate_errors,
+        return_norms: request.return_norms,
+        timeout: request_timeout(request.timeout_ms)?,
+        truncation: request.truncation,
+        no_cache,
+    };
     
-    let embedding_models = state.embedding_service
-        .create_embeddings(texts.clone(), &request.task)
-        .await?;
+    let output = match (inputs, &request.tasks) {
+        (EmbeddingInputs::Texts(texts), Some(tasks)) => state.embedding_service
+            .create_weighted_embeddings(texts, tasks, request.model.as_deref(), options)
+            .await?,
+        (EmbeddingInputs::Texts(texts), None) => state.embedding_service
+            .create_embeddings(texts, &request.task, request.model.as_deref(), options)
+            .await?,
+        (EmbeddingInputs::TokenIds(_), Some(_)) => {
+            return Err(AppError::Validation("tasks cannot be combined with token id input".to_string()));
+        }
+        (EmbeddingInputs::TokenIds(token_ids), None) => state.embedding_service
+            .create_embeddings_from_tokens(token_ids, &request.task, request.model.as_deref(), options)
+            .await?,
+    };
+    if single_input && Settings::get().strict_input_shape {
+        check_single_output(&output)?;
+    }
+    let model_version = state.embedding_service.model_version(request.model.as_deref()).await;
+
+    let encoding_format = match response_format {
+        ResponseFormat::Json(encoding_format) => encoding_format,
+        ResponseFormat::Arrow => {
+            let body = embeddings_to_arrow(&output.embeddings)?;
+            info!("Successfully processed embedding request for {} texts (arrow)", input_count);
+            let response = ([(header::CONTENT_TYPE, ARROW_STREAM_MEDIA_TYPE)], body).into_response();
+            return Ok(with_no_store(with_model_version(response, model_version), no_cache));
+        }
+    };
+
+    // Debug details are only exposed on deployments that opt into admin routes
+    let debug = (request.debug && Settings::get().enable_admin_routes).then_some(EmbeddingDebug {
+        output_tensors: output.output_tensors,
+        batch_shapes: output.batch_shapes,
+    });
+
+    let request_params = request.echo_params.then(|| EmbeddingRequestParams {
+        model: model.clone(),
+        task: request.task.clone(),
+        tasks: request.tasks.clone(),
+        encoding_format: encoding_format.as_str().to_string(),
+        normalized,
+        truncation: request.truncation,
+        include_usage: request.include_usage,
+        input_count,
+    });
+
+    let prompt_tokens = output.prompt_tokens;
+    let response = EmbeddingResponse {
+        object: "list".to_string(),
+        data: embedding_data(output.embeddings, encoding_format),
+        model,
+        created: unix_timestamp(),
+        usage: request.include_usage.then_some(EmbeddingUsage {
+            prompt_tokens,
+            total_tokens: prompt_tokens,
+        }),
+        debug,
+        request_params,
+        skipped_indices: output.skipped_indices,
+        warnings: output.failed_inputs,
+    };
+
+    info!("Successfully processed embedding request for {} texts", input_count);
+    Ok(with_no_store(with_model_version(Json(response).into_response(), model_version), no_cache))
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/embeddings/pair",
+    tag = "Embeddings",
+    request_body(
+        content = EmbeddingPairRequest,
+        description = "Query embedded with retrieval.query and passages embedded with retrieval.passage",
+    ),
+    responses(
+        (status = 200, description = "Successfully generated query and passage embeddings", body = EmbeddingPairResponse),
+        (status = 400, description = "Bad request - invalid encoding format, input or unknown model"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Embedding model not ready or Triton unavailable")
+    )
+)]
+pub async fn create_embedding_pair(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonBody(request): JsonBody<EmbeddingPairRequest>,
+) -> Result<Response, AppError> {
+    if Settings::get().precheck_readiness {
+        state.embedding_service.ensure_ready().await?;
+    }
 
-    let embedding_data: Vec<EmbeddingData> = embedding_models
+    // Arrow is not offered for the pair layout, so it falls back to float JSON
+    let encoding_format = match negotiate(&headers, request.encoding_format.as_deref())? {
+        ResponseFormat::Json(encoding_format) => encoding_format,
+        ResponseFormat::Arrow => EncodingFormat::Float,
+    };
+    let no_cache = wants_no_cache(&headers, request.no_cache);
+    if request.passages.is_empty() {
+        return Err(AppError::Validation("Passages cannot be empty".to_string()));
+    }
+    check_batch_items("passages", request.passages.len())?;
+    let passage_count = request.passages.len();
+
+    let options = EmbeddingOptions {
+        normalize: request.normalized.unwrap_or(true),
+        no_cache,
+        ..EmbeddingOptions::default()
+    };
+    let (query_output, passage_output) = tokio::try_join!(
+        state.embedding_service.create_embeddings(vec![request.query], QUERY_TASK, request.model.as_deref(), options),
+        state.embedding_service.create_embeddings(request.passages, PASSAGE_TASK, request.model.as_deref(), options),
+    )?;
+    let model_version = state.embedding_service.model_version(request.model.as_deref()).await;
+
+    let model = state.embedding_service.resolve_model(request.model.as_deref())?.to_string();
+    let prompt_tokens = query_output.prompt_tokens + passage_output.prompt_tokens;
+    let query = embedding_data(query_output.embeddings, encoding_format)
+        .into_iter()
+        .next()
+        .ok_or_else(|| AppError::Validation("Query is empty".to_string()))?;
+
+    let response = EmbeddingPairResponse {
+        object: "embedding.pair".to_string(),
+        query,
+        passages: embedding_data(passage_output.embeddings, encoding_format),
+        skipped_indices: passage_output.skipped_indices,
+        model,
+        usage: request.include_usage.then_some(EmbeddingUsage {
+            prompt_tokens,
+            total_tokens: prompt_tokens,
+        }),
+    };
+
+    info!("Successfully processed embedding pair request for 1 query and {} passages", passage_count);
+    Ok(with_no_store(with_model_version(Json(response).into_response(), model_version), no_cache))
+}
+
+/// Under `strict_input_shape`, a single-string input must yield exactly one
+/// embedding rather than an empty `data` array.
+fn check_single_output(output: &EmbeddingOutput) -> Result<(), AppError> {
+    if output.embeddings.len() == 1 {
+        return Ok(());
+    }
+    let reason = output.failed_inputs
+        .first()
+        .map_or_else(|| "input is empty".to_string(), |warning| warning.message.clone());
+    Err(AppError::Validation(format!(
+        "Single input produced no embedding: {}",
+        reason
+    )))
+}
+
+/// Response items for `embeddings`, every one in the request's encoding format.
+fn embedding_data(embeddings: Vec<EmbeddingModel>, encoding_format: EncodingFormat) -> Vec<EmbeddingData> {
+    embeddings
         .into_iter()
         .map(|model| EmbeddingData {
             object: "embedding".to_string(),
-            embedding: model.vector,
+            embedding: encoding_format.encode(model.vector),
             index: model.index,
+            norm: model.norm,
         })
-        .collect();
+        .collect()
+}
 
-    let response = EmbeddingResponse {
-        object: "list".to_string(),
-        data: embedding_data,
-        model: request.model,
-        usage: EmbeddingUsage {
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EmbeddingVector, InputText, InputWarning};
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+
+    fn output(embeddings: usize, failed_inputs: Vec<InputWarning>) -> EmbeddingOutput {
+        EmbeddingOutput {
+            embeddings: (0..embeddings)
+                .map(|index| EmbeddingModel { vector: vec![1.0], index, norm: None })
+                .collect(),
+            output_tensors: Vec::new(),
+            batch_shapes: Vec::new(),
+            skipped_indices: Vec::new(),
             prompt_tokens: 0,
-            total_tokens: 0,
-        },
-    };
+            failed_inputs,
+        }
+    }
+
+    #[test]
+    fn string_input_is_a_single_input() {
+        let input: InputText = serde_json::from_str(r#""Xin chào""#).unwrap();
+        assert!(input.is_single());
+        match input.into_inputs() {
+            EmbeddingInputs::Texts(texts) => assert_eq!(texts, ["Xin chào"]),
+            other => panic!("expected texts, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn array_input_is_not_single_even_with_one_item() {
+        let one: InputText = serde_json::from_str(r#"["Xin chào"]"#).unwrap();
+        assert!(!one.is_single());
+        assert_eq!(one.into_inputs().len(), 1);
+
+        let many: InputText = serde_json::from_str(r#"["a", "b"]"#).unwrap();
+        assert!(!many.is_single());
+        assert_eq!(many.into_inputs().len(), 2);
+
+        let empty: InputText = serde_json::from_str("[]").unwrap();
+        assert!(empty.into_inputs().is_empty());
+    }
+
+    #[test]
+    fn strict_single_input_requires_one_embedding() {
+        assert!(check_single_output(&output(1, Vec::new())).is_ok());
+
+        let warning = InputWarning { index: 0, message: "too long".to_string() };
+        let error = check_single_output(&output(0, vec![warning])).unwrap_err();
+        assert!(matches!(&error, AppError::Validation(message) if message.contains("too long")));
+    }
+
+    #[test]
+    fn base64_format_applies_to_every_item() {
+        let vectors = vec![vec![1.0_f32, -2.5], vec![0.25, 3.0], vec![0.0, 1.0]];
+        let embeddings = vectors
+            .iter()
+            .enumerate()
+            .map(|(index, vector)| EmbeddingModel { vector: vector.clone(), index, norm: None })
+            .collect();
 
-    info!("Successfully processed embedding request for {} texts", texts.len());
-    Ok(Json(response))
+        let data = embedding_data(embeddings, EncodingFormat::Base64);
+        assert_eq!(data.len(), vectors.len());
+        for (item, vector) in data.iter().zip(&vectors) {
+            let EmbeddingVector::Base64(text) = &item.embedding else {
+                panic!("expected a base64 embedding at index {}, got {:?}", item.index, item.embedding);
+            };
+            let decoded: Vec<f32> = BASE64
+                .decode(text)
+                .unwrap()
+                .chunks_exact(4)
+                .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect();
+            assert_eq!(&decoded, vector);
+        }
+    }
 }
@@ -0,0 +1,225 @@
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap, StatusCode},
+};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::time::Duration;
+
+use crate::config::{InvalidUnicodeHandling, Settings};
+use crate::error::AppError;
+
+const REPLACEMENT_ESCAPE: &[u8] = b"\\uFFFD";
+
+/// JSON body extractor that applies the configured handling of invalid
+/// UTF-8 and lone UTF-16 surrogate escapes before deserializing, and reports
+/// failures as `AppError::Validation`. Bodies over `max_request_body_bytes`
+/// that had no `Content-Length` fail while being read and get `413` as well.
+pub struct JsonBody<T>(pub T);
+
+impl<T, S> FromRequest<S> for JsonBody<T>
+where
+    T: DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        check_content_type(req.headers())?;
+
+        let bytes = Bytes::from_request(req, state).await.map_err(|e| {
+            if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+                AppError::PayloadTooLarge(e.body_text())
+            } else {
+                AppError::Validation(format!("Failed to read request body: {}", e))
+            }
+        })?;
+
+        let body = match Settings::get().invalid_unicode_handling {
+            InvalidUnicodeHandling::Reject => {
+                check_unicode(&bytes)?;
+                Cow::Borrowed(bytes.as_ref())
+            }
+            InvalidUnicodeHandling::Replace => Cow::Owned(sanitize_unicode(&bytes)),
+        };
+
+        serde_json::from_slice(&body)
+            .map(JsonBody)
+            .map_err(|e| AppError::Validation(format!("Invalid JSON body: {}", e)))
+    }
+}
+
+/// Rejects request arrays longer than `max_batch_items`, naming the field.
+pub fn check_batch_items(field: &str, count: usize) -> Result<(), AppError> {
+    match Settings::get().max_batch_items {
+        Some(limit) if count > limit => Err(AppError::Validation(format!(
+            "Too many items in `{}`: {} provided, max_batch_items is {}",
+            field, count, limit
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Per-request Triton timeout from a request's `timeout_ms`; zero is rejected.
+pub fn request_timeout(timeout_ms: Option<u64>) -> Result<Option<Duration>, AppError> {
+    match timeout_ms {
+        Some(0) => Err(AppError::Validation("timeout_ms must be greater than 0".to_string())),
+        timeout_ms => Ok(timeout_ms.map(Duration::from_millis)),
+    }
+}
+
+/// Whether the `Accept` header lists the given media type.
+pub fn accepts_media_type(headers: &HeaderMap, media_type: &str) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media| media.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case(media_type))
+}
+
+/// Requires `application/json` (or an `application/*+json` type). In lenient
+/// mode `text/json` and a missing header are accepted as well.
+fn check_content_type(headers: &HeaderMap) -> Result<(), AppError> {
+    let lenient = Settings::get().lenient_content_type;
+
+    let Some(content_type) = headers.get(header::CONTENT_TYPE) else {
+        if lenient {
+            return Ok(());
+        }
+        return Err(AppError::Validation(
+            "Expected request with `Content-Type: application/json`".to_string(),
+        ));
+    };
+
+    let content_type = content_type.to_str().unwrap_or("");
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+
+    let is_json = mime == "application/json"
+        || (mime.starts_with("application/") && mime.ends_with("+json"))
+        || (lenient && mime == "text/json");
+
+    if is_json {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "Unsupported Content-Type '{}', expected application/json",
+            content_type
+        )))
+    }
+}
+
+/// Fails on invalid UTF-8 or a `\uXXXX` escape encoding a lone surrogate.
+fn check_unicode(body: &[u8]) -> Result<(), AppError> {
+    if let Err(e) = std::str::from_utf8(body) {
+        return Err(AppError::Validation(format!(
+            "Request body contains invalid UTF-8 at byte {}",
+            e.valid_up_to()
+        )));
+    }
+
+    if let Some(offset) = find_lone_surrogates(body).first() {
+        return Err(AppError::Validation(format!(
+            "Request body contains a lone UTF-16 surrogate escape at byte {}",
+            offset
+        )));
+    }
+
+    Ok(())
+}
+
+/// Replaces invalid UTF-8 sequences and lone surrogate escapes with U+FFFD.
+fn sanitize_unicode(body: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(body).into_owned().into_bytes();
+    let lone = find_lone_surrogates(&text);
+    if lone.is_empty() {
+        return text;
+    }
+
+    let mut sanitized = Vec::with_capacity(text.len());
+    let mut last = 0;
+    for offset in lone {
+        sanitized.extend_from_slice(&text[last..offset]);
+        sanitized.extend_from_slice(REPLACEMENT_ESCAPE);
+        last = offset + REPLACEMENT_ESCAPE.len();
+    }
+    sanitized.extend_from_slice(&text[last..]);
+    sanitized
+}
+
+/// Byte offsets of `\uXXXX` escapes that encode an unpaired surrogate.
+fn find_lone_surrogates(body: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut i = 0;
+
+    while i < body.len() {
+        if body[i] != b'\\' {
+            i += 1;
+            continue;
+        }
+
+        let Some(unit) = parse_unicode_escape(body, i) else {
+            // Any other escape, including `\\`, consumes the next byte
+            i += 2;
+            continue;
+        };
+
+        match unit {
+            0xD800..=0xDBFF => {
+                let paired = parse_unicode_escape(body, i + 6)
+                    .is_some_and(|next| (0xDC00..=0xDFFF).contains(&next));
+                if paired {
+                    i += 12;
+                    continue;
+                }
+                offsets.push(i);
+            }
+            0xDC00..=0xDFFF => offsets.push(i),
+            _ => {}
+        }
+        i += 6;
+    }
+
+    offsets
+}
+
+fn parse_unicode_escape(body: &[u8], at: usize) -> Option<u16> {
+    let escape = body.get(at..at + 6)?;
+    if escape[0] != b'\\' || escape[1] != b'u' {
+        return None;
+    }
+    let hex = std::str::from_utf8(&escape[2..]).ok()?;
+    u16::from_str_radix(hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, routing::post, Router};
+    use futures_util::stream;
+    use tower::Service;
+    use tower_http::limit::RequestBodyLimitLayer;
+
+    #[tokio::test]
+    async fn chunked_body_over_the_limit_is_payload_too_large() {
+        let mut router = Router::new()
+            .route("/", post(|JsonBody(_): JsonBody<serde_json::Value>| async {}))
+            .layer(RequestBodyLimitLayer::new(16));
+
+        // A streamed body has no Content-Length, so the limit is only hit while reading it
+        let chunks = ["[\"0123456789\",", " \"0123456789\"]"].map(|chunk| Ok::<_, std::io::Error>(Bytes::from(chunk)));
+        let request = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from_stream(stream::iter(chunks)))
+            .unwrap();
+
+        let response = router.call(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,62 @@
+use axum::http::{header, HeaderMap};
+
+use crate::api::arrow::ARROW_STREAM_MEDIA_TYPE;
+use crate::error::AppError;
+use crate::models::EncodingFormat;
+
+/// `Accept` media type selecting float array embeddings in JSON
+pub const FLOAT_JSON_MEDIA_TYPE: &str = "application/vnd.embeddings.float+json";
+
+/// `Accept` media type selecting base64 embeddings in JSON
+pub const BASE64_JSON_MEDIA_TYPE: &str = "application/vnd.embeddings.base64+json";
+
+/// Response representation chosen for an embedding request
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResponseFormat {
+    Json(EncodingFormat),
+    Arrow,
+}
+
+/// Picks the response format. An explicit `encoding_format` body field wins,
+/// then the most preferred supported `Accept` media type, then float JSON.
+pub fn negotiate(headers: &HeaderMap, encoding_format: Option<&str>) -> Result<ResponseFormat, AppError> {
+    if let Some(format) = encoding_format {
+        return EncodingFormat::parse(format).map(ResponseFormat::Json);
+    }
+
+    Ok(accepted_format(headers).unwrap_or(ResponseFormat::Json(EncodingFormat::Float)))
+}
+
+/// Supported format with the highest `q` in the `Accept` header; on ties the
+/// first listed wins.
+fn accepted_format(headers: &HeaderMap) -> Option<ResponseFormat> {
+    let mut best: Option<(ResponseFormat, f32)> = None;
+
+    let entries = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+
+    for entry in entries {
+        let mut parts = entry.split(';').map(str::trim);
+        let media = parts.next().unwrap_or("").to_ascii_lowercase();
+        let quality = parts
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        let format = match media.as_str() {
+            "application/json" | FLOAT_JSON_MEDIA_TYPE => ResponseFormat::Json(EncodingFormat::Float),
+            BASE64_JSON_MEDIA_TYPE => ResponseFormat::Json(EncodingFormat::Base64),
+            ARROW_STREAM_MEDIA_TYPE => ResponseFormat::Arrow,
+            _ => continue,
+        };
+
+        if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+            best = Some((format, quality));
+        }
+    }
+
+    best.map(|(format, _)| format)
+}
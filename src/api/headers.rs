@@ -0,0 +1,43 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue},
+    response::Response,
+};
+
+/// Response header carrying the Triton model version that served a request
+pub const MODEL_VERSION_HEADER: &str = "x-model-version";
+
+/// Request and response header carrying the id a request is logged under
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Adds the `X-Model-Version` header when the version is known.
+pub fn with_model_version(mut response: Response, version: Option<String>) -> Response {
+    if let Some(value) = version.and_then(|v| HeaderValue::from_str(&v).ok()) {
+        response.headers_mut().insert(MODEL_VERSION_HEADER, value);
+    }
+    response
+}
+
+/// Whether the client asked to bypass caching, via a `no_cache` body flag or
+/// a `Cache-Control: no-store` / `no-cache` request header.
+pub fn wants_no_cache(headers: &HeaderMap, no_cache: bool) -> bool {
+    no_cache
+        || headers
+            .get_all(header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .any(|directive| {
+                directive.eq_ignore_ascii_case("no-store") || directive.eq_ignore_ascii_case("no-cache")
+            })
+}
+
+/// Marks a response as not to be stored when the client bypassed caching.
+pub fn with_no_store(mut response: Response, no_cache: bool) -> Response {
+    if no_cache {
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+    response
+}
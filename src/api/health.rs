@@ -1,24 +1,39 @@
 use axum::{
-    extract::State,
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
     Json,
 };
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
+use crate::config::Settings;
+use crate::metrics::Metrics;
 use crate::services::{
     embedding_service::EmbeddingService,
+    lifecycle::{Lifecycle, LifecycleState},
     reranking_service::RerankingService,
 };
 
 pub struct AppState {
     pub embedding_service: Arc<EmbeddingService>,
     pub reranking_service: Arc<RerankingService>,
+    pub lifecycle: Arc<Lifecycle>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct HealthQuery {
+    /// Query Triton now instead of using a cached readiness result
+    #[serde(default)]
+    pub fresh: bool,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct HealthResponse {
     pub status: String,
+    /// Lifecycle state: "starting", "warming", "ready" or "degraded"
+    pub state: LifecycleState,
     pub embedding_service: ServiceStatus,
     pub reranking_service: ServiceStatus,
 }
@@ -32,18 +47,20 @@ pub struct ServiceStatus {
     get,
     path = "/health",
     tag = "Health",
+    params(HealthQuery),
     responses(
         (status = 200, description = "Service health status", body = HealthResponse)
     )
 )]
 pub async fn health_check(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<HealthQuery>,
 ) -> Json<HealthResponse> {
-    let embedding_ready = state.embedding_service.is_ready().await.unwrap_or(false);
-    let reranking_ready = state.reranking_service.is_ready().await.unwrap_or(false);
+    let (embedding_ready, reranking_ready) = model_readiness(&state, query.fresh).await;
 
     Json(HealthResponse {
         status: "ok".to_string(),
+        state: state.lifecycle.get(),
         embedding_service: ServiceStatus {
             ready: embedding_ready,
         },
@@ -52,3 +69,95 @@ pub async fn health_check(
         },
     })
 }
+
+#[derive(Serialize, ToSchema)]
+pub struct LivenessResponse {
+    /// Always "alive"
+    pub status: String,
+}
+
+/// Answers without touching Triton, so a liveness probe only restarts the
+/// process when the process itself is stuck.
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    tag = "Health",
+    responses(
+        (status = 200, description = "The process is up and serving HTTP", body = LivenessResponse)
+    )
+)]
+pub async fn liveness_check() -> Json<LivenessResponse> {
+    Json(LivenessResponse {
+        status: "alive".to_string(),
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    /// "ready", "not_ready" or "degraded"
+    pub status: String,
+    pub state: LifecycleState,
+    pub embedding_service: ServiceStatus,
+    pub reranking_service: ServiceStatus,
+    /// Share of failed requests over the error-rate window
+    pub error_rate: f64,
+}
+
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    tag = "Health",
+    params(HealthQuery),
+    responses(
+        (status = 200, description = "Both models can serve", body = ReadinessResponse),
+        (status = 503, description = "A model is not ready, or the recent error rate exceeds the configured threshold", body = ReadinessResponse)
+    )
+)]
+pub async fn readiness_check(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HealthQuery>,
+) -> Response {
+    let settings = Settings::get();
+    let (embedding_ready, reranking_ready) = model_readiness(&state, query.fresh).await;
+
+    let (error_rate, requests) = Metrics::get().recent_error_rate(settings.error_rate_window_secs);
+    let degraded = settings.error_rate_unhealthy_threshold.is_some_and(|threshold| {
+        requests >= settings.error_rate_min_requests && error_rate > threshold
+    });
+
+    let lifecycle = state.lifecycle.get();
+
+    let (status_code, status) = if lifecycle == LifecycleState::Degraded {
+        (StatusCode::SERVICE_UNAVAILABLE, "degraded")
+    } else if lifecycle != LifecycleState::Ready || !(embedding_ready && reranking_ready) {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    } else if degraded {
+        (StatusCode::SERVICE_UNAVAILABLE, "degraded")
+    } else {
+        (StatusCode::OK, "ready")
+    };
+
+    let body = Json(ReadinessResponse {
+        status: status.to_string(),
+        state: lifecycle,
+        embedding_service: ServiceStatus {
+            ready: embedding_ready,
+        },
+        reranking_service: ServiceStatus {
+            ready: reranking_ready,
+        },
+        error_rate,
+    });
+
+    (status_code, body).into_response()
+}
+
+/// Readiness of both models, bypassing any cached result when `fresh`.
+async fn model_readiness(state: &AppState, fresh: bool) -> (bool, bool) {
+    let (embedding, reranking) = if fresh {
+        tokio::join!(state.embedding_service.check_ready(), state.reranking_service.check_ready())
+    } else {
+        tokio::join!(state.embedding_service.is_ready(), state.reranking_service.is_ready())
+    };
+    (embedding.unwrap_or(false), reranking.unwrap_or(false))
+}
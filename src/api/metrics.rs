@@ -0,0 +1,19 @@
+use axum::{
+    http::header,
+    response::{IntoResponse, Response},
+};
+
+use crate::metrics::Metrics;
+
+/// Content type of the Prometheus text exposition format
+const PROMETHEUS_MEDIA_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Request counts, error counts and latency histograms for Prometheus to
+/// scrape. Public like `/health`.
+pub async fn prometheus_metrics() -> Response {
+    (
+        [(header::CONTENT_TYPE, PROMETHEUS_MEDIA_TYPE)],
+        Metrics::get().render_prometheus(),
+    )
+        .into_response()
+}
@@ -1,40 +1,131 @@
+pub mod admin;
+pub mod arrow;
+pub mod extract;
+pub mod format;
+pub mod headers;
 pub mod health;
+pub mod metrics;
 pub mod embeddings;
+pub mod models;
 pub mod reranking;
+pub mod retrieval;
 pub mod openapi;
 
 use axum::{
+    extract::DefaultBodyLimit,
+    http::{header, HeaderName, HeaderValue, Method},
     middleware,
     routing::{get, post},
     Router,
 };
 use std::sync::Arc;
+use tower_http::{
+    compression::{
+        predicate::{NotForContentType, Predicate, SizeAbove},
+        CompressionLayer,
+    },
+    cors::{AllowOrigin, CorsLayer},
+    limit::RequestBodyLimitLayer,
+};
+use tracing::warn;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
-use health::{AppState, health_check};
-use embeddings::create_embeddings;
+use health::{AppState, health_check, liveness_check, readiness_check};
+use embeddings::{create_embeddings, create_embedding_pair};
+use metrics::prometheus_metrics;
+use models::list_models;
 use reranking::rerank_documents;
+use retrieval::retrieve;
 use openapi::ApiDoc;
-use crate::middleware::{auth_middleware, logging_middleware};
+use admin::{flush_cache, get_metrics, reload_tokenizers, run_loadtest};
+use crate::api::headers::{MODEL_VERSION_HEADER, REQUEST_ID_HEADER};
+use crate::config::Settings;
+use crate::middleware::{
+    auth_middleware, logging_middleware, metrics_middleware, request_id_middleware,
+};
 
 pub fn create_router(state: Arc<AppState>) -> Router {
     // Create protected API routes with auth middleware
-    let protected_routes = Router::new()
+    let mut protected_routes = Router::new()
         .route("/v1/embeddings", post(create_embeddings))
+        .route("/v1/embeddings/pair", post(create_embedding_pair))
         .route("/v1/rerank", post(rerank_documents))
+        .route("/v1/retrieve", post(retrieve));
+
+    // Admin routes only exist when explicitly enabled
+    if Settings::get().enable_admin_routes {
+        protected_routes = protected_routes
+            .route("/admin/loadtest", post(run_loadtest))
+            .route("/admin/metrics", get(get_metrics))
+            .route("/admin/flush-cache", post(flush_cache))
+            .route("/admin/reload-tokenizers", post(reload_tokenizers));
+    }
+
+    let protected_routes = protected_routes
         .layer(middleware::from_fn(auth_middleware))
+        .layer(middleware::from_fn(metrics_middleware))
         .with_state(state.clone());
 
-    // Public routes (health check and swagger)
+    // Public routes (health check, metrics, model listing and swagger)
     let public_routes = Router::new()
         .route("/health", get(health_check))
+        .route("/metrics", get(prometheus_metrics))
+        .route("/v1/models", get(list_models))
+        .route("/health/live", get(liveness_check))
+        .route("/health/ready", get(readiness_check))
         .with_state(state);
 
     // Merge all routes and add logging middleware
-    Router::new()
+    let router = Router::new()
         .merge(protected_routes)
         .merge(public_routes)
-        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
+        .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()));
+
+    // Enforced before the body is buffered, replacing axum's fixed 2 MiB default
+    let router = router
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(Settings::get().max_request_body_bytes));
+
+    let router = if Settings::get().enable_compression {
+        // Streamed NDJSON is left alone so each line still reaches the client as it is produced
+        let predicate = SizeAbove::new(Settings::get().compression_min_bytes)
+            .and(NotForContentType::const_new("application/x-ndjson"));
+        router.layer(CompressionLayer::new().gzip(true).compress_when(predicate))
+    } else {
+        router
+    };
+
+    // Outside auth so browser preflights (which carry no credentials) succeed
+    let router = match &Settings::get().cors_allowed_origins {
+        Some(origins) => router.layer(cors_layer(origins)),
+        None => router,
+    };
+
+    router
         .layer(middleware::from_fn(logging_middleware))
+        .layer(middleware::from_fn(request_id_middleware))
+}
+
+/// CORS for `origins`, where `*` allows any origin. Origins that are not
+/// valid header values are skipped with a warning.
+fn cors_layer(origins: &[String]) -> CorsLayer {
+    let allow_origin = if origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        AllowOrigin::list(origins.iter().filter_map(|origin| {
+            HeaderValue::from_str(origin)
+                .inspect_err(|_| warn!("Ignoring invalid CORS origin '{}'", origin))
+                .ok()
+        }))
+    };
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE, HeaderName::from_static(REQUEST_ID_HEADER)])
+        .expose_headers([
+            HeaderName::from_static(MODEL_VERSION_HEADER),
+            HeaderName::from_static(REQUEST_ID_HEADER),
+        ])
 }
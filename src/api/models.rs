@@ -0,0 +1,70 @@
+use axum::{extract::State, Json};
+use serde::Serialize;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use crate::api::health::AppState;
+use crate::config::Settings;
+
+#[derive(Serialize, ToSchema)]
+pub struct ModelList {
+    /// Always "list"
+    pub object: String,
+    pub data: Vec<ModelInfo>,
+}
+
+/// One served model, in the OpenAI models API shape
+#[derive(Serialize, ToSchema)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Always "model"
+    pub object: String,
+    /// Creation time as a Unix timestamp; not tracked, so always 0
+    pub created: u64,
+    pub owned_by: String,
+    /// Whether Triton reports the model ready to serve
+    pub ready: bool,
+}
+
+impl ModelInfo {
+    fn new(id: &str, ready: bool) -> Self {
+        Self {
+            id: id.to_string(),
+            object: "model".to_string(),
+            created: 0,
+            owned_by: "system".to_string(),
+            ready,
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/models",
+    tag = "Models",
+    responses(
+        (status = 200, description = "The configured embedding models, including routed ones, and the reranker model", body = ModelList)
+    )
+)]
+pub async fn list_models(State(state): State<Arc<AppState>>) -> Json<ModelList> {
+    let settings = Settings::get();
+    let embedding_models = state.embedding_service.model_names();
+    let (embedding_ready, reranking_ready) = tokio::join!(
+        futures_util::future::join_all(
+            embedding_models.iter().map(|model| state.embedding_service.is_model_ready(model)),
+        ),
+        state.reranking_service.is_ready(),
+    );
+
+    let mut data: Vec<ModelInfo> = embedding_models
+        .iter()
+        .zip(embedding_ready)
+        .map(|(model, ready)| ModelInfo::new(model, ready))
+        .collect();
+    data.push(ModelInfo::new(&settings.reranker_model_name, reranking_ready.unwrap_or(false)));
+
+    Json(ModelList {
+        object: "list".to_string(),
+        data,
+    })
+}
@@ -1,10 +1,14 @@
 use utoipa::OpenApi;
 
 use crate::models::{
-    EmbeddingRequest, InputText, EmbeddingResponse, EmbeddingData, EmbeddingUsage,
-    RerankRequest, DocumentInput, RerankResponse, RerankResult, RerankUsage,
+    EmbeddingRequest, TaskWeight, Truncation, InputText, EmbeddingResponse, EmbeddingData, EmbeddingVector, EmbeddingUsage,
+    EmbeddingDebug, BatchShape, EmbeddingRequestParams, InputWarning, OutputTensorInfo, EmbeddingPairRequest, EmbeddingPairResponse,
+    RerankRequest, DocumentInput, ScoreFormat, RerankResponse, RerankResult, RerankUsage,
+    RetrieveRequest, RetrieveResponse, RetrieveResult,
 };
-use crate::api::health::{HealthResponse, ServiceStatus};
+use crate::api::health::{HealthResponse, LivenessResponse, ReadinessResponse, ServiceStatus};
+use crate::api::models::{ModelInfo, ModelList};
+use crate::services::lifecycle::LifecycleState;
 
 #[derive(OpenApi)]
 #[openapi(
@@ -15,30 +19,57 @@ use crate::api::health::{HealthResponse, ServiceStatus};
     ),
     paths(
         crate::api::health::health_check,
+        crate::api::health::liveness_check,
+        crate::api::health::readiness_check,
+        crate::api::models::list_models,
         crate::api::embeddings::create_embeddings,
+        crate::api::embeddings::create_embedding_pair,
         crate::api::reranking::rerank_documents,
+        crate::api::retrieval::retrieve,
     ),
     components(
         schemas(
             // Health schemas
             HealthResponse,
+            LivenessResponse,
+            ReadinessResponse,
             ServiceStatus,
+            LifecycleState,
+            // Model schemas
+            ModelList,
+            ModelInfo,
             // Embedding schemas
             EmbeddingRequest,
+            TaskWeight,
+            Truncation,
             InputText,
             EmbeddingResponse,
             EmbeddingData,
+            EmbeddingVector,
             EmbeddingUsage,
+            EmbeddingDebug,
+            BatchShape,
+            EmbeddingRequestParams,
+            InputWarning,
+            OutputTensorInfo,
+            EmbeddingPairRequest,
+            EmbeddingPairResponse,
             // Reranking schemas
             RerankRequest,
             DocumentInput,
+            ScoreFormat,
             RerankResponse,
             RerankResult,
             RerankUsage,
+            // Retrieval schemas
+            RetrieveRequest,
+            RetrieveResponse,
+            RetrieveResult,
         )
     ),
     tags(
         (name = "Health", description = "Health check endpoints"),
+        (name = "Models", description = "Model discovery endpoints"),
         (name = "Embeddings", description = "Text embedding generation endpoints"),
         (name = "Reranking", description = "Document reranking endpoints")
     )
@@ -1,16 +1,29 @@
 use axum::{
+    body::{Body, Bytes},
     extract::State,
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
     Json,
 };
+use futures_util::StreamExt;
+use serde_json::json;
+use std::convert::Infallible;
 use std::sync::Arc;
-use tracing::info;
+use tracing::{info, error, Span};
 
+use crate::api::extract::{accepts_media_type, check_batch_items, request_timeout, JsonBody};
+use crate::api::headers::with_model_version;
 use crate::api::health::AppState;
+use crate::config::Settings;
 use crate::error::AppError;
+use crate::metrics::Metrics;
 use crate::models::{
-    RerankRequest, RerankResponse, RerankResult, RerankUsage,
+    DocumentInput, RerankModel, RerankOptions, RerankRequest, RerankResponse, RerankResult, RerankUsage,
 };
 
+/// Media type for newline-delimited JSON streaming responses
+const NDJSON_MEDIA_TYPE: &str = "application/x-ndjson";
+
 #[utoipa::path(
     post,
     path = "/v1/rerank",
@@ -18,46 +31,125 @@ use crate::models::{
     request_body = RerankRequest,
     responses(
         (status = 200, description = "Successfully reranked documents", body = RerankResponse),
-        (status = 400, description = "Bad request"),
-        (status = 500, description = "Internal server error")
+        (status = 200, description = "With `Accept: application/x-ndjson`, one unsorted result per line, streamed per Triton batch. `top_n` and `offset` are ignored and `softmax` scores are rejected. A failed batch emits an `{\"error\": ...}` line.", content_type = "application/x-ndjson"),
+        (status = 400, description = "Bad request, e.g. empty documents, `top_n` of 0 or `offset` past the last document"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "Reranker model not ready or Triton unavailable")
     )
 )]
 pub async fn rerank_documents(
     State(state): State<Arc<AppState>>,
-    Json(request): Json<RerankRequest>,
-) -> Result<Json<RerankResponse>, AppError> {
+    headers: HeaderMap,
+    JsonBody(request): JsonBody<RerankRequest>,
+) -> Result<Response, AppError> {
+    Metrics::get()
+        .rerank_requests
+        .track(rerank(state, headers, request))
+        .await
+}
+
+async fn rerank(
+    state: Arc<AppState>,
+    headers: HeaderMap,
+    request: RerankRequest,
+) -> Result<Response, AppError> {
+    if Settings::get().precheck_readiness {
+        state.reranking_service.ensure_ready().await?;
+    }
+
+    check_batch_items("documents", request.documents.len())?;
+    let timeout = request_timeout(request.timeout_ms)?;
+
+    let text_field = request.document_text_field
+        .as_deref()
+        .unwrap_or(&Settings::get().document_text_field);
+    let text_field = Some(text_field).filter(|field| !field.is_empty());
     let documents: Vec<String> = request.documents
         .iter()
-        .map(|doc| doc.as_text())
-        .collect();
+        .enumerate()
+        .map(|(index, doc)| doc.as_text(index, text_field))
+        .collect::<Result<_, _>>()?;
 
-    let result_models = state.reranking_service
+    let model_version = state.reranking_service.model_version().await;
+
+    if accepts_media_type(&headers, NDJSON_MEDIA_TYPE) {
+        let originals = request.documents;
+        let span = Span::current();
+        let stream = state.reranking_service.clone()
+            .rerank_documents_stream(
+                request.query,
+                documents,
+                request.return_documents,
+                request.score_format,
+                timeout,
+            )
+            .await?
+            .map(move |chunk| {
+                let _entered = span.enter();
+                let mut lines = String::new();
+                match chunk {
+                    Ok(models) => {
+                        for result in models.into_iter().map(|model| to_result(model, &originals)) {
+                            lines.push_str(&json!(result).to_string());
+                            lines.push('\n');
+                        }
+                    }
+                    Err(e) => {
+                        error!("Streaming rerank batch failed: {}", e);
+                        lines.push_str(&json!({ "error": e.to_string() }).to_string());
+                        lines.push('\n');
+                    }
+                }
+                Ok::<_, Infallible>(Bytes::from(lines))
+            });
+
+        let response = (
+            [(header::CONTENT_TYPE, NDJSON_MEDIA_TYPE)],
+            Body::from_stream(stream),
+        ).into_response();
+        return Ok(with_model_version(response, model_version));
+    }
+
+    let output = state.reranking_service
         .rerank_documents(
             request.query.clone(),
             documents.clone(),
-            request.top_n,
-            request.return_documents,
+            RerankOptions {
+                top_n: request.top_n,
+                offset: request.offset,
+                return_documents: request.return_documents,
+                score_format: request.score_format,
+                timeout,
+            },
         )
         .await?;
 
-    let results: Vec<RerankResult> = result_models
+    let results: Vec<RerankResult> = output.results
         .into_iter()
-        .map(|model| RerankResult {
-            index: model.index,
-            relevance_score: model.relevance_score,
-            document: model.document,
-        })
+        .map(|model| to_result(model, &request.documents))
         .collect();
 
     let response = RerankResponse {
         object: "list".to_string(),
         data: results,
         model: request.model,
-        usage: RerankUsage {
-            total_tokens: 0,
-        },
+        usage: request.include_usage.then_some(RerankUsage {
+            total_tokens: output.total_tokens,
+        }),
     };
 
     info!("Successfully reranked {} documents", documents.len());
-    Ok(Json(response))
+    Ok(with_model_version(Json(response).into_response(), model_version))
+}
+
+/// Converts a scored document, echoing the original request document (not
+/// the extracted text) when documents are returned.
+fn to_result(model: RerankModel, originals: &[DocumentInput]) -> RerankResult {
+    RerankResult {
+        index: model.index,
+        relevance_score: model.relevance_score,
+        document: model.document
+            .and(originals.get(model.index))
+            .map(DocumentInput::to_value),
+    }
 }
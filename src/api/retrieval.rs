@@ -0,0 +1,123 @@
+use axum::{extract::State, Json};
+use std::sync::Arc;
+use tracing::info;
+
+use crate::api::extract::{check_batch_items, JsonBody};
+use crate::api::health::AppState;
+use crate::config::Settings;
+use crate::error::AppError;
+use crate::models::{EmbeddingOptions, RetrieveRequest, RetrieveResponse, RetrieveResult, RerankOptions};
+
+const QUERY_TASK: &str = "retrieval.query";
+const PASSAGE_TASK: &str = "retrieval.passage";
+
+#[utoipa::path(
+    post,
+    path = "/v1/retrieve",
+    tag = "Reranking",
+    request_body = RetrieveRequest,
+    responses(
+        (status = 200, description = "Documents ranked by embedding similarity and/or reranker score", body = RetrieveResponse),
+        (status = 400, description = "Bad request - both stages disabled or invalid input"),
+        (status = 500, description = "Internal server error"),
+        (status = 503, description = "A model is not ready or Triton unavailable")
+    )
+)]
+pub async fn retrieve(
+    State(state): State<Arc<AppState>>,
+    JsonBody(request): JsonBody<RetrieveRequest>,
+) -> Result<Json<RetrieveResponse>, AppError> {
+    let settings = Settings::get();
+
+    if !request.embedding_filter && !request.rerank {
+        return Err(AppError::Validation(
+            "At least one of embedding_filter and rerank must be enabled".to_string(),
+        ));
+    }
+    if request.documents.is_empty() {
+        return Err(AppError::Validation("Documents cannot be empty".to_string()));
+    }
+    check_batch_items("documents", request.documents.len())?;
+    if request.candidate_count == 0 {
+        return Err(AppError::Validation("candidate_count must be at least 1".to_string()));
+    }
+
+    if settings.precheck_readiness {
+        if request.embedding_filter {
+            state.embedding_service.ensure_ready().await?;
+        }
+        if request.rerank {
+            state.reranking_service.ensure_ready().await?;
+        }
+    }
+
+    // (document index, similarity, rerank score) for the surviving candidates
+    let mut candidates: Vec<(usize, Option<f32>, Option<f32>)> = if request.embedding_filter {
+        let (query_output, document_output) = tokio::try_join!(
+            state.embedding_service.create_embeddings(vec![request.query.clone()], QUERY_TASK, None, EmbeddingOptions::default()),
+            state.embedding_service.create_embeddings(request.documents.clone(), PASSAGE_TASK, None, EmbeddingOptions::default()),
+        )?;
+        let query_vector = query_output.embeddings
+            .into_iter()
+            .next()
+            .map(|model| model.vector)
+            .ok_or_else(|| AppError::Validation("Query is empty".to_string()))?;
+
+        let mut scored: Vec<(usize, Option<f32>, Option<f32>)> = document_output.embeddings
+            .iter()
+            .map(|model| (model.index, Some(dot(&query_vector, &model.vector)), None))
+            .collect();
+        scored.sort_by(|a, b| b.1.unwrap_or(0.0).total_cmp(&a.1.unwrap_or(0.0)));
+        scored.truncate(request.candidate_count);
+        scored
+    } else {
+        (0..request.documents.len()).map(|index| (index, None, None)).collect()
+    };
+
+    if request.rerank {
+        let survivors: Vec<String> = candidates
+            .iter()
+            .map(|(index, _, _)| request.documents[*index].clone())
+            .collect();
+        let reranked = state.reranking_service
+            .rerank_documents(request.query.clone(), survivors, RerankOptions::default())
+            .await?
+            .results;
+
+        // Reranker indices refer to the survivor list; map them back
+        candidates = reranked
+            .into_iter()
+            .map(|model| {
+                let (index, similarity, _) = candidates[model.index];
+                (index, similarity, Some(model.relevance_score))
+            })
+            .collect();
+    }
+
+    if let Some(top_n) = request.top_n {
+        candidates.truncate(top_n);
+    }
+
+    let data: Vec<RetrieveResult> = candidates
+        .into_iter()
+        .map(|(index, similarity_score, rerank_score)| RetrieveResult {
+            index,
+            similarity_score,
+            rerank_score,
+            document: request.return_documents.then(|| request.documents[index].clone()),
+        })
+        .collect();
+
+    info!("Retrieved {} of {} documents", data.len(), request.documents.len());
+    Ok(Json(RetrieveResponse {
+        object: "list".to_string(),
+        data,
+        embedding_model: settings.embedding_model_name.clone(),
+        reranker_model: settings.reranker_model_name.clone(),
+    }))
+}
+
+/// Cosine similarity of two unit-length vectors.
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
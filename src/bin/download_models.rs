@@ -6,17 +6,104 @@ This script downloads ONNX model files from Jina AI repositories:
 - jinaai/jina-reranker-v2-base-multilingual
 
 Models are saved to the Triton model repository structure.
+
+Each file gets a timeout scaled to its size, capped by
+DOWNLOAD_MAX_FILE_TIMEOUT_SECS (default 3600). A download that receives no
+data for DOWNLOAD_STALL_TIMEOUT_SECS (default 60) is aborted.
+
+By default every file is attempted and failures are reported at the end.
+Pass --fail-fast to stop at the first failed file instead.
+
+Set HF_TOKEN to download from gated or private repositories.
+
+Files stored in Git LFS (the ONNX weights) are checked against the sha256
+Hugging Face reports for them, both when already present and after
+downloading. A mismatching download is retried up to DOWNLOAD_ATTEMPTS
+times (default 3).
+
+Up to DOWNLOAD_CONCURRENCY files (default 3) of a model download at once.
+Data is written to `<file>.part` and renamed when complete; an interrupted
+download resumes from the partial file with an HTTP range request, both on
+the next attempt and on the next run.
+
+Each model is fetched from its `main` branch unless EMBEDDINGS_REVISION or
+RERANKER_REVISION names another branch, tag or commit sha. Pin a commit sha
+for reproducible deployments.
 */
 
+use std::collections::HashMap;
 use std::fs;
-use std::io::Write;
-use std::path::Path;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use anyhow::{Context, Result};
+use futures_util::{stream, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use tokio::time::Instant;
+
+#[path = "../hf_hub.rs"]
+mod hf_hub;
+
+use hf_hub::HF_BASE_URL;
+
+/// Assumed worst-case throughput when scaling a file's timeout to its size
+const MIN_BYTES_PER_SEC: u64 = 1024 * 1024;
+
+/// Base time allowed for any file on top of its size-scaled share
+const BASE_FILE_TIMEOUT_SECS: u64 = 30;
+
+/// Appended to a file's name while it is being downloaded
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// Time limits for downloads, overridable via environment variables
+struct DownloadLimits {
+    /// Upper bound on the time allowed for a single file (DOWNLOAD_MAX_FILE_TIMEOUT_SECS)
+    max_file_timeout: Duration,
+    /// Abort when no bytes arrive for this long (DOWNLOAD_STALL_TIMEOUT_SECS)
+    stall_timeout: Duration,
+    /// Downloads per file before a checksum mismatch is fatal (DOWNLOAD_ATTEMPTS)
+    attempts: u64,
+    /// Files of a model downloaded at once (DOWNLOAD_CONCURRENCY)
+    concurrency: usize,
+}
 
-const HF_BASE_URL: &str = "https://huggingface.co";
+impl DownloadLimits {
+    fn from_env() -> Self {
+        let secs = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+        Self {
+            max_file_timeout: Duration::from_secs(secs("DOWNLOAD_MAX_FILE_TIMEOUT_SECS", 3600)),
+            stall_timeout: Duration::from_secs(secs("DOWNLOAD_STALL_TIMEOUT_SECS", 60)),
+            attempts: secs("DOWNLOAD_ATTEMPTS", 3).max(1),
+            concurrency: secs("DOWNLOAD_CONCURRENCY", 3).max(1) as usize,
+        }
+    }
+
+    /// Time allowed for a file of the given size, capped by `max_file_timeout`.
+    /// Unknown sizes get the full cap for ONNX weights and the base timeout
+    /// for small metadata files.
+    fn file_timeout(&self, file_path: &str, size: Option<u64>) -> Duration {
+        let secs = match size {
+            Some(size) => BASE_FILE_TIMEOUT_SECS + size / MIN_BYTES_PER_SEC,
+            None if file_path.ends_with(".onnx") => self.max_file_timeout.as_secs(),
+            None => BASE_FILE_TIMEOUT_SECS,
+        };
+        Duration::from_secs(secs).min(self.max_file_timeout)
+    }
+}
 
 struct ModelDownload {
     repo_id: &'static str,
+    /// Branch, tag or commit to download, unless `revision_env` is set
+    revision: &'static str,
+    revision_env: &'static str,
     files: &'static [&'static str],
     target_dir: &'static str,
     model_name: &'static str,
@@ -25,6 +112,8 @@ struct ModelDownload {
 impl ModelDownload {
     const EMBEDDINGS: ModelDownload = ModelDownload {
         repo_id: "jinaai/jina-embeddings-v3",
+        revision: "main",
+        revision_env: "EMBEDDINGS_REVISION",
         files: &[
             "onnx/model_fp16.onnx",
             "tokenizer.json",
@@ -38,6 +127,8 @@ impl ModelDownload {
 
     const RERANKER: ModelDownload = ModelDownload {
         repo_id: "jinaai/jina-reranker-v2-base-multilingual",
+        revision: "main",
+        revision_env: "RERANKER_REVISION",
         files: &[
             "onnx/model_fp16.onnx",
             "tokenizer.json",
@@ -48,6 +139,15 @@ impl ModelDownload {
         target_dir: "model_repository/jina-reranker-v2/1",
         model_name: "Jina Reranker v2 Base Multilingual",
     };
+
+    /// The revision to download, URL-encoded so refs like `refs/pr/1` work.
+    fn revision(&self) -> String {
+        std::env::var(self.revision_env)
+            .ok()
+            .filter(|revision| !revision.is_empty())
+            .unwrap_or_else(|| self.revision.to_string())
+            .replace('/', "%2F")
+    }
 }
 
 fn ensure_dir(path: &Path) -> Result<()> {
@@ -55,11 +155,84 @@ fn ensure_dir(path: &Path) -> Result<()> {
         .with_context(|| format!("Failed to create directory: {}", path.display()))
 }
 
+/// sha256 of each Git LFS file in the repository, keyed by path, from the
+/// Hugging Face model API. Files kept in plain Git have no entry.
+async fn expected_digests(repo_id: &str, revision: &str, client: &reqwest::Client) -> Result<HashMap<String, String>> {
+    let url = format!("{}/api/models/{}/revision/{}?blobs=true", HF_BASE_URL, repo_id, revision);
+    let response = client.get(&url)
+        .timeout(Duration::from_secs(BASE_FILE_TIMEOUT_SECS))
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch file metadata from {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Failed to fetch file metadata from {}", url))?;
+    let info: serde_json::Value = response.json().await.context("Invalid model metadata")?;
+
+    Ok(info["siblings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|sibling| {
+            let path = sibling["rfilename"].as_str()?;
+            let sha256 = sibling["lfs"]["sha256"].as_str()?;
+            Some((path.to_string(), sha256.to_ascii_lowercase()))
+        })
+        .collect())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Feeds the file's contents to `hasher`.
+fn hash_file(path: &Path, hasher: &mut Sha256) -> Result<()> {
+    let mut file = fs::File::open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            return Ok(());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+fn file_sha256(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    hash_file(path, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+fn size_display(size: u64) -> String {
+    if size > 1024 * 1024 {
+        format!("{:.2} MB", size as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.2} KB", size as f64 / 1024.0)
+    }
+}
+
+/// Prints a line without garbling the progress bars of concurrent downloads.
+fn say(progress: &MultiProgress, message: String) {
+    progress.suspend(|| println!("{}", message));
+}
+
+fn partial_path(target_file: &Path) -> PathBuf {
+    let mut name = target_file.as_os_str().to_os_string();
+    name.push(PARTIAL_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Downloads `file_path` from `base_url`, the repository's `resolve/<revision>` URL.
 async fn download_file(
-    repo_id: &str,
+    base_url: &str,
     file_path: &str,
     target_dir: &Path,
     client: &reqwest::Client,
+    limits: &DownloadLimits,
+    expected_sha256: Option<&str>,
+    progress: &MultiProgress,
 ) -> Result<bool> {
     let file_name = Path::new(file_path)
         .file_name()
@@ -70,111 +243,238 @@ async fn download_file(
         if let Ok(metadata) = fs::metadata(&target_file) {
             let file_size = metadata.len();
             let min_size = if file_path.ends_with(".onnx") { 1024 * 1024 } else { 100 }; // 1MB for ONNX, 100 bytes for others
-            if file_size > min_size {
-                let file_size_display = if file_size > 1024 * 1024 {
-                    format!("{:.2} MB", file_size as f64 / (1024.0 * 1024.0))
-                } else {
-                    format!("{:.2} KB", file_size as f64 / 1024.0)
-                };
-                println!("  {} already exists ({})", file_name.to_string_lossy(), file_size_display);
+            let intact = match expected_sha256 {
+                Some(expected) => file_sha256(&target_file)? == expected,
+                None => file_size > min_size,
+            };
+            if intact {
+                let verified = if expected_sha256.is_some() { ", sha256 verified" } else { "" };
+                say(progress, format!("  {} already exists ({}{})", file_name.to_string_lossy(), size_display(file_size), verified));
                 return Ok(true);
             } else {
-                println!("  {} corrupted, re-downloading...", file_name.to_string_lossy());
+                say(progress, format!("  {} corrupted, re-downloading...", file_name.to_string_lossy()));
+                fs::remove_file(&target_file).ok();
             }
         }
     }
 
-    let download_url = format!(
-        "{}/{}/resolve/main/{}",
-        HF_BASE_URL, repo_id, file_path
-    );
+    let download_url = format!("{}/{}", base_url, file_path);
+    let partial = partial_path(&target_file);
 
-    println!("  Downloading {}...", file_name.to_string_lossy());
+    for attempt in 1..=limits.attempts {
+        say(progress, format!("  Downloading {}...", file_name.to_string_lossy()));
+        let digest = match fetch_file(file_path, &download_url, &target_file, client, limits, progress).await {
+            Ok(digest) => digest,
+            // Retry only once data has arrived, so the next attempt resumes
+            // it; errors such as 401 and 404 fail right away
+            Err(e) if attempt < limits.attempts && partial.exists() => {
+                say(progress, format!("  {} interrupted: {:#}", file_name.to_string_lossy(), e));
+                say(progress, format!("  Resuming ({}/{})...", attempt + 1, limits.attempts));
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
 
-    let response = client
-        .get(&download_url)
-        .send()
+        let Some(expected) = expected_sha256 else {
+            break;
+        };
+        if digest == expected {
+            say(progress, format!("  sha256 verified for {}: {}", file_name.to_string_lossy(), digest));
+            break;
+        }
+
+        say(progress, format!(
+            "  Checksum mismatch for {}:\n    expected sha256: {}\n    computed sha256: {}",
+            file_name.to_string_lossy(),
+            expected,
+            digest
+        ));
+        fs::remove_file(&target_file).ok();
+        if attempt == limits.attempts {
+            anyhow::bail!(
+                "{} failed sha256 verification after {} attempts",
+                file_name.to_string_lossy(),
+                limits.attempts
+            );
+        }
+        say(progress, format!("  Retrying ({}/{})...", attempt + 1, limits.attempts));
+    }
+
+    if target_file.exists() {
+        let file_size = fs::metadata(&target_file)?.len();
+        say(progress, format!("  Downloaded {} ({})", file_name.to_string_lossy(), size_display(file_size)));
+        Ok(true)
+    } else {
+        say(progress, format!("  Failed to download {}", file_name.to_string_lossy()));
+        Ok(false)
+    }
+}
+
+/// Streams one file to its `.part` file, resuming from the bytes already
+/// there when the server honors the range request, and renames it to
+/// `target_file` once complete. Returns the sha256 of the whole file.
+async fn fetch_file(
+    file_path: &str,
+    download_url: &str,
+    target_file: &Path,
+    client: &reqwest::Client,
+    limits: &DownloadLimits,
+    progress: &MultiProgress,
+) -> Result<String> {
+    let partial = partial_path(target_file);
+    let resume_from = fs::metadata(&partial).map_or(0, |metadata| metadata.len());
+
+    let mut request = client.get(download_url);
+    if resume_from > 0 {
+        request = request.header(RANGE, format!("bytes={}-", resume_from));
+    }
+
+    let started = Instant::now();
+    let response = tokio::time::timeout(limits.file_timeout(file_path, None), request.send())
         .await
+        .with_context(|| format!("Timed out waiting for a response from {}", download_url))?
         .with_context(|| format!("Failed to download from {}", download_url))?;
 
-    if !response.status().is_success() {
-        anyhow::bail!(
-            "Failed to download: HTTP status {}",
-            response.status()
-        );
+    match response.status() {
+        status if status.is_success() => {}
+        status @ (StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+            let hint = if std::env::var_os("HF_TOKEN").is_some() {
+                "check that HF_TOKEN is valid and has been granted access to the repository"
+            } else {
+                "the repository may be gated or private; set HF_TOKEN to a token with access"
+            };
+            anyhow::bail!("Authentication failed (HTTP {}) for {}: {}", status, download_url, hint);
+        }
+        StatusCode::NOT_FOUND => {
+            anyhow::bail!("Not found (HTTP 404): {}; check the repository and file path", download_url);
+        }
+        StatusCode::RANGE_NOT_SATISFIABLE => {
+            fs::remove_file(&partial).ok();
+            anyhow::bail!("Server rejected resuming from byte {}; discarded the partial file", resume_from);
+        }
+        status => anyhow::bail!("Failed to download: HTTP status {}", status),
     }
 
-    let total_size = response.content_length().unwrap_or(0);
-    let mut file = fs::File::create(&target_file)
-        .with_context(|| format!("Failed to create file: {}", target_file.display()))?;
-
+    // A 200 instead of 206 means the server ignored the range; start over
+    let mut hasher = Sha256::new();
     let mut downloaded: u64 = 0;
+    let mut file = if response.status() == StatusCode::PARTIAL_CONTENT {
+        hash_file(&partial, &mut hasher)?;
+        downloaded = resume_from;
+        fs::OpenOptions::new().append(true).open(&partial)
+    } else {
+        fs::File::create(&partial)
+    }
+    .with_context(|| format!("Failed to open file: {}", partial.display()))?;
+
+    let total_size = response.content_length().map_or(0, |remaining| downloaded + remaining);
+    let file_timeout = limits.file_timeout(file_path, response.content_length());
+    let deadline = started + file_timeout;
     let mut stream = response.bytes_stream();
 
-    use futures_util::StreamExt;
-    
     let show_progress = total_size > 10 * 1024 * 1024; // > 10MB
-    
-    while let Some(chunk) = stream.next().await {
+    let bar = show_progress.then(|| {
+        let bar = progress.add(ProgressBar::new(total_size));
+        bar.set_style(
+            ProgressStyle::with_template("    {msg} {bar:30} {percent:>3}% {bytes}/{total_bytes}")
+                .expect("valid progress template"),
+        );
+        bar.set_message(file_path.to_string());
+        bar.set_position(downloaded);
+        bar
+    });
+
+    loop {
+        let wait = limits.stall_timeout.min(deadline.saturating_duration_since(Instant::now()));
+        let next = match tokio::time::timeout(wait, stream.next()).await {
+            Ok(next) => next,
+            Err(_) if Instant::now() >= deadline => {
+                anyhow::bail!("Download exceeded its {}s time limit", file_timeout.as_secs());
+            }
+            Err(_) => {
+                anyhow::bail!("Download stalled: no data received for {}s", limits.stall_timeout.as_secs());
+            }
+        };
+        let Some(chunk) = next else {
+            break;
+        };
         let chunk = chunk.context("Error reading chunk")?;
         file.write_all(&chunk)
             .context("Error writing to file")?;
-        
+        hasher.update(&chunk);
+
         downloaded += chunk.len() as u64;
-        
-        if show_progress && total_size > 0 {
-            let progress = (downloaded as f64 / total_size as f64) * 100.0;
-            print!("\r    Progress: {:.1}%", progress);
-            std::io::stdout().flush().ok();
+
+        if let Some(bar) = &bar {
+            bar.set_position(downloaded);
         }
     }
-    
-    if show_progress {
-        println!();
-    }
 
-    if target_file.exists() {
-        let file_size = fs::metadata(&target_file)?.len();
-        let size_display = if file_size > 1024 * 1024 {
-            format!("{:.2} MB", file_size as f64 / (1024.0 * 1024.0))
-        } else {
-            format!("{:.2} KB", file_size as f64 / 1024.0)
-        };
-        println!("  Downloaded {} ({})", file_name.to_string_lossy(), size_display);
-        Ok(true)
-    } else {
-        println!("  Failed to download {}", file_name.to_string_lossy());
-        Ok(false)
+    if let Some(bar) = bar {
+        bar.finish_and_clear();
     }
+
+    file.flush().context("Error writing to file")?;
+    drop(file);
+    fs::rename(&partial, target_file)
+        .with_context(|| format!("Failed to move {} into place", partial.display()))?;
+
+    Ok(hex(&hasher.finalize()))
 }
 
-async fn download_model(model: &ModelDownload) -> Result<bool> {
+async fn download_model(model: &ModelDownload, limits: &DownloadLimits, fail_fast: bool) -> Result<bool> {
     println!("\n{}", "=".repeat(50));
     println!("{}", model.model_name);
     println!("{}", "=".repeat(50));
+    let revision = model.revision();
     println!("Repository: {}", model.repo_id);
+    println!("Revision: {}", revision);
     println!("Target: {}", model.target_dir);
     println!();
 
     let target_dir = Path::new(model.target_dir);
     ensure_dir(target_dir)?;
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(3600))
+    let client = hf_hub::client_builder()
+        .context("HF_TOKEN contains characters that are not valid in an HTTP header")?
         .build()?;
 
+    let base_url = format!("{}/{}/resolve/{}", HF_BASE_URL, model.repo_id, revision);
+    let digests = expected_digests(model.repo_id, &revision, &client).await.unwrap_or_else(|e| {
+        println!("  Warning: sha256 checksums unavailable, ONNX files will not be verified: {:#}", e);
+        HashMap::new()
+    });
+
+    let progress = MultiProgress::new();
+    let mut downloads = stream::iter(model.files)
+        .map(|file_path| {
+            let expected_sha256 = digests.get(*file_path).map(String::as_str);
+            let (base_url, client, progress) = (&base_url, &client, &progress);
+            async move {
+                let result = download_file(
+                    base_url, file_path, target_dir, client, limits, expected_sha256, progress,
+                ).await;
+                (file_path, result)
+            }
+        })
+        .buffer_unordered(limits.concurrency);
+
     let mut success_count = 0;
     let mut fail_count = 0;
 
-    for file_path in model.files {
-        match download_file(model.repo_id, file_path, target_dir, &client).await {
+    while let Some((file_path, result)) = downloads.next().await {
+        match result {
             Ok(true) => success_count += 1,
             Ok(false) => fail_count += 1,
             Err(e) => {
-                println!("  Error downloading {}: {}", file_path, e);
+                say(&progress, format!("  Error downloading {}: {}", file_path, e));
                 fail_count += 1;
             }
         }
+
+        if fail_fast && fail_count > 0 {
+            anyhow::bail!("{} failed, cancelling remaining files (--fail-fast)", file_path);
+        }
     }
 
     println!("\n{} files ready, {} failed", success_count, fail_count);
@@ -187,19 +487,27 @@ async fn main() -> Result<()> {
     println!("Jina AI Models Download Script");
     println!("{}", "=".repeat(60));
 
-    let embeddings_success = download_model(&ModelDownload::EMBEDDINGS)
+    let limits = DownloadLimits::from_env();
+    let fail_fast = std::env::args().skip(1).any(|arg| arg == "--fail-fast");
+
+    let embeddings_success = download_model(&ModelDownload::EMBEDDINGS, &limits, fail_fast)
         .await
         .unwrap_or_else(|e| {
             eprintln!("Embeddings download failed: {}", e);
             false
         });
 
-    let reranker_success = download_model(&ModelDownload::RERANKER)
-        .await
-        .unwrap_or_else(|e| {
-            eprintln!("Reranker download failed: {}", e);
-            false
-        });
+    let reranker_success = if fail_fast && !embeddings_success {
+        println!("\nSkipping {} (--fail-fast)", ModelDownload::RERANKER.model_name);
+        false
+    } else {
+        download_model(&ModelDownload::RERANKER, &limits, fail_fast)
+            .await
+            .unwrap_or_else(|e| {
+                eprintln!("Reranker download failed: {}", e);
+                false
+            })
+    };
 
     println!("\n{}", "=".repeat(60));
     if embeddings_success && reranker_success {
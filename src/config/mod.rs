@@ -1,13 +1,203 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::sync::OnceLock;
 
 static SETTINGS: OnceLock<Settings> = OnceLock::new();
 
+/// What to do with request bodies containing invalid UTF-8 or lone surrogates
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvalidUnicodeHandling {
+    /// Reject the request with a validation error
+    #[default]
+    Reject,
+    /// Substitute U+FFFD for each invalid sequence
+    Replace,
+}
+
+impl std::str::FromStr for InvalidUnicodeHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reject" => Ok(InvalidUnicodeHandling::Reject),
+            "replace" => Ok(InvalidUnicodeHandling::Replace),
+            other => Err(format!("unknown invalid unicode handling '{}'", other)),
+        }
+    }
+}
+
+/// What to do with embedding inputs that are empty after preprocessing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptyInputHandling {
+    /// Reject the request with a validation error
+    #[default]
+    Error,
+    /// Return an all-zero vector of the output dimension without calling Triton
+    Zero,
+    /// Omit the input from the response; other items keep their original index
+    Skip,
+}
+
+impl std::str::FromStr for EmptyInputHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "error" => Ok(EmptyInputHandling::Error),
+            "zero" => Ok(EmptyInputHandling::Zero),
+            "skip" => Ok(EmptyInputHandling::Skip),
+            other => Err(format!("unknown empty input handling '{}'", other)),
+        }
+    }
+}
+
+/// What to do with literal special tokens (e.g. `[SEP]`, `</s>`) found in
+/// rerank query or document text, which the tokenizer would otherwise turn
+/// into real special tokens
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SeparatorHandling {
+    /// Leave the text unchanged
+    Keep,
+    /// Remove the special token strings
+    Strip,
+    /// Break them up with a zero-width space so they tokenize as plain text
+    #[default]
+    Escape,
+}
+
+impl std::str::FromStr for SeparatorHandling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "keep" => Ok(SeparatorHandling::Keep),
+            "strip" => Ok(SeparatorHandling::Strip),
+            "escape" => Ok(SeparatorHandling::Escape),
+            other => Err(format!("unknown separator handling '{}'", other)),
+        }
+    }
+}
+
+/// Wire protocol used to talk to Triton
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TritonProtocol {
+    /// KServe v2 JSON over HTTP
+    #[default]
+    Http,
+    /// KServe v2 gRPC with binary tensor contents
+    Grpc,
+}
+
+impl std::str::FromStr for TritonProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "http" => Ok(TritonProtocol::Http),
+            "grpc" => Ok(TritonProtocol::Grpc),
+            other => Err(format!("unknown Triton protocol '{}'", other)),
+        }
+    }
+}
+
+/// HTTP version used for Triton requests over the `http` protocol
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TritonHttpVersion {
+    /// Let reqwest decide; HTTP/1.1 for plain `http://` URLs
+    #[default]
+    Auto,
+    /// HTTP/1.1 only, one request per pooled connection at a time
+    Http1,
+    /// HTTP/2 with prior knowledge, multiplexing requests over a connection
+    Http2,
+}
+
+impl std::str::FromStr for TritonHttpVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(TritonHttpVersion::Auto),
+            "http1" => Ok(TritonHttpVersion::Http1),
+            "http2" => Ok(TritonHttpVersion::Http2),
+            other => Err(format!("unknown Triton HTTP version '{}'", other)),
+        }
+    }
+}
+
+/// What to do with a rerank query longer than `max_query_tokens`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryOverflow {
+    /// Keep the first `max_query_tokens` tokens and log a warning
+    #[default]
+    Truncate,
+    /// Reject the request with a validation error
+    Reject,
+}
+
+impl std::str::FromStr for QueryOverflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "truncate" => Ok(QueryOverflow::Truncate),
+            "reject" => Ok(QueryOverflow::Reject),
+            other => Err(format!("unknown query overflow handling '{}'", other)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Settings {
     #[serde(default = "default_triton_url")]
     pub triton_url: String,
 
+    pub triton_fallback_url: Option<String>,
+
+    #[serde(default)]
+    pub triton_protocol: TritonProtocol,
+
+    /// Extra attempts for inference calls failing with connection errors or 5xx responses
+    #[serde(default = "default_triton_max_retries")]
+    pub triton_max_retries: u32,
+
+    /// Delay before the first retry, doubled for each further one
+    #[serde(default = "default_triton_retry_backoff_ms")]
+    pub triton_retry_backoff_ms: u64,
+
+    /// Send and receive HTTP inference tensors as raw bytes (Triton's binary data extension)
+    #[serde(default = "default_triton_binary_data")]
+    pub triton_binary_data: bool,
+
+    /// Triton gRPC address, used when `triton_protocol` is `grpc`
+    #[serde(default = "default_triton_grpc_url")]
+    pub triton_grpc_url: String,
+
+    #[serde(default)]
+    pub triton_startup_probe_fatal: bool,
+
+    #[serde(default)]
+    pub output_name_check_fatal: bool,
+
+    #[serde(default)]
+    pub precheck_readiness: bool,
+
+    /// Warmup requests per model before reporting ready (0 skips warmup)
+    #[serde(default = "default_warmup_requests")]
+    pub warmup_requests: usize,
+
+    #[serde(default)]
+    pub triton_extra_headers: HashMap<String, String>,
+
+    #[serde(default)]
+    pub triton_http_version: TritonHttpVersion,
+
     #[serde(default = "default_timeout")]
     pub triton_http_connection_timeout: u64,
 
@@ -17,12 +207,26 @@ pub struct Settings {
     #[serde(default = "default_embedding_model")]
     pub embedding_model_name: String,
 
+    /// Triton embedding models by the request `model` that selects them,
+    /// served alongside `embedding_model_name`
+    #[serde(default)]
+    pub embedding_model_routes: HashMap<String, String>,
+
     #[serde(default = "default_reranker_model")]
     pub reranker_model_name: String,
 
+    /// Output tensor read from the embedding model; an ONNX export artifact
+    #[serde(default = "default_embedding_output_name")]
+    pub embedding_output_name: String,
+
+    #[serde(default = "default_reranker_output_name")]
+    pub reranker_output_name: String,
+
+    /// Tokenizer source when `tokenizer_file` is unset: a local path or a Hugging Face repo id
     #[serde(default = "default_tokenizer_path")]
     pub tokenizer_path: String,
 
+    /// Reranker tokenizer source when `reranker_tokenizer_file` is unset
     #[serde(default = "default_reranker_tokenizer_path")]
     pub reranker_tokenizer_path: String,
 
@@ -30,18 +234,172 @@ pub struct Settings {
 
     pub reranker_tokenizer_file: Option<String>,
 
+    /// Directory where tokenizers downloaded from Hugging Face are kept, so
+    /// restarts load them from disk. Empty disables the cache.
+    #[serde(default = "default_tokenizer_cache_dir")]
+    pub tokenizer_cache_dir: String,
+
     #[serde(default = "default_max_sequence_length")]
     pub max_sequence_length: usize,
 
     #[serde(default = "default_reranker_max_sequence_length")]
     pub reranker_max_sequence_length: usize,
 
+    /// Token budget for the rerank query, leaving the rest of the sequence to documents
+    pub max_query_tokens: Option<usize>,
+
+    #[serde(default)]
+    pub query_overflow: QueryOverflow,
+
+    pub fixed_sequence_length: Option<usize>,
+
     #[serde(default = "default_max_batch")]
     pub embedding_client_max_batch: usize,
 
+    pub max_inputs_per_request: Option<usize>,
+
+    /// Process-wide cap on rerank documents being scored at once; each
+    /// request waits for one permit per document
+    pub max_concurrent_rerank_documents: Option<usize>,
+
+    /// Cap on array elements (`input`, `passages`, `documents`) in any request
+    pub max_batch_items: Option<usize>,
+
+    /// Largest accepted request body; bigger bodies are rejected with `413`
+    #[serde(default = "default_max_request_body_bytes")]
+    pub max_request_body_bytes: usize,
+
+    /// Maximum embedding values (inputs x dimensions) in a single response
+    pub max_response_items: Option<usize>,
+
+    pub max_batch_tokens: Option<usize>,
+
+    /// Batch embedding inputs shortest first instead of in input order
+    #[serde(default)]
+    pub sort_batches_by_length: bool,
+
+    #[serde(default = "default_max_batch")]
+    pub reranker_client_max_batch: usize,
+
+    #[serde(default)]
+    pub reranker_separator_handling: SeparatorHandling,
+
+    /// Text joining query and document into one sequence, for reranker
+    /// tokenizers without a pair template. Unset uses pair encoding.
+    pub reranker_separator: Option<String>,
+
+    pub max_request_memory_bytes: Option<u64>,
+
+    /// Embeddings kept in the in-memory LRU cache; 0 disables it
+    #[serde(default)]
+    pub embedding_cache_size: usize,
+
+    /// Embed each distinct input text once per request
+    #[serde(default)]
+    pub dedup_inputs: bool,
+
+    #[serde(default)]
+    pub adaptive_batching: bool,
+
+    #[serde(default = "default_adaptive_batching_target_latency_ms")]
+    pub adaptive_batching_target_latency_ms: u64,
+
+    pub enabled_tasks: Option<Vec<String>>,
+
+    /// Key holding the text to rerank in object documents, unless the
+    /// request names one. Empty reranks objects as serialized JSON.
+    #[serde(default = "default_document_text_field")]
+    pub document_text_field: String,
+
+    pub fallback_embedding_dim: Option<usize>,
+
+    /// Model output dimension verified by the startup consistency check
+    pub expected_embedding_dim: Option<usize>,
+
+    #[serde(default)]
+    pub consistency_check_fatal: bool,
+
+    /// JSON matrix (`output_dim` rows of `model_dim` floats) applied to every embedding
+    pub projection_matrix_path: Option<String>,
+
+    #[serde(default)]
+    pub preprocess_strip_bom: bool,
+
+    #[serde(default)]
+    pub preprocess_trim_trailing_whitespace: bool,
+
+    #[serde(default)]
+    pub preprocess_lowercase: bool,
+
+    #[serde(default)]
+    pub preprocess_collapse_whitespace: bool,
+
+    #[serde(default)]
+    pub preprocess_strip: bool,
+
+    #[serde(default)]
+    pub invalid_unicode_handling: InvalidUnicodeHandling,
+
+    #[serde(default)]
+    pub empty_input_vector: EmptyInputHandling,
+
+    /// Embed and rerank empty or whitespace-only inputs like any other text
+    /// instead of applying `empty_input_vector` / rejecting the document
+    #[serde(default)]
+    pub allow_blank_inputs: bool,
+
+    /// Send the pair encoding's `token_type_ids` to the reranker as a third
+    /// Triton input, for models exported with one
+    #[serde(default)]
+    pub reranker_token_type_ids: bool,
+
+    /// Single-string inputs must produce exactly one embedding; skipped or
+    /// failed single inputs become a 400 instead of an empty `data` array
+    #[serde(default)]
+    pub strict_input_shape: bool,
+
+    #[serde(default = "default_lenient_content_type")]
+    pub lenient_content_type: bool,
+
+    /// Serialize embedding floats in fixed decimal notation instead of
+    /// serde_json's shortest form, which may use an exponent (`1.2e-5`)
+    #[serde(default)]
+    pub fixed_decimal_floats: bool,
+
+    /// Gzip responses for clients sending `Accept-Encoding: gzip`
+    #[serde(default)]
+    pub enable_compression: bool,
+
+    /// Responses smaller than this are sent uncompressed (at most 65535)
+    #[serde(default = "default_compression_min_bytes")]
+    pub compression_min_bytes: u16,
+
+    /// Replace NaN/Inf in embeddings instead of failing the request
+    #[serde(default)]
+    pub sanitize_output: bool,
+
+    #[serde(default = "default_server_host")]
+    pub server_host: String,
+
+    #[serde(default = "default_server_port")]
+    pub server_port: u16,
+
+    /// Poll Triton readiness in the background at this interval and answer
+    /// health checks and readiness prechecks from the cached result
+    #[serde(default)]
+    pub readiness_poll_secs: Option<u64>,
+
+    /// Reuse a live readiness result for this many seconds
+    pub health_cache_ttl_secs: Option<u64>,
+
+    /// Seconds in-flight requests get to finish after SIGTERM/Ctrl-C
+    #[serde(default = "default_shutdown_grace_secs")]
+    pub shutdown_grace_secs: u64,
+
     #[serde(default = "default_api_title")]
     pub api_title: String,
 
+    #[allow(dead_code)]
     #[serde(default = "default_api_description")]
     pub api_description: String,
 
@@ -50,14 +408,43 @@ pub struct Settings {
 
     pub api_key: Option<String>,
 
+    /// Accepted API keys by consumer label; `api_key` is accepted too, as
+    /// `default`
+    #[serde(default)]
+    pub api_keys: HashMap<String, String>,
+
     #[serde(default)]
     pub require_api_key: bool,
+
+    /// Origins allowed to call the API from a browser, or `*` for any.
+    /// No CORS headers are sent when unset.
+    pub cors_allowed_origins: Option<Vec<String>>,
+
+    #[serde(default)]
+    pub enable_admin_routes: bool,
+
+    #[serde(default = "default_model_version_header")]
+    pub model_version_header: bool,
+
+    /// Report `/health/ready` as degraded when the recent server error rate exceeds this (0.0-1.0)
+    pub error_rate_unhealthy_threshold: Option<f64>,
+
+    #[serde(default = "default_error_rate_window_secs")]
+    pub error_rate_window_secs: u64,
+
+    /// Requests needed within the window before the error rate is considered
+    #[serde(default = "default_error_rate_min_requests")]
+    pub error_rate_min_requests: u64,
 }
 
 fn default_triton_url() -> String {
     "triton:8000".to_string()
 }
 
+fn default_triton_grpc_url() -> String {
+    "triton:8001".to_string()
+}
+
 fn default_timeout() -> u64 {
     300
 }
@@ -70,10 +457,26 @@ fn default_reranker_model() -> String {
     "jina-reranker-v2".to_string()
 }
 
+fn default_embedding_output_name() -> String {
+    "13049".to_string()
+}
+
+fn default_reranker_output_name() -> String {
+    "logits".to_string()
+}
+
 fn default_tokenizer_path() -> String {
     "jinaai/jina-embeddings-v3".to_string()
 }
 
+fn default_document_text_field() -> String {
+    "text".to_string()
+}
+
+fn default_tokenizer_cache_dir() -> String {
+    "tokenizer_cache".to_string()
+}
+
 fn default_reranker_tokenizer_path() -> String {
     "jinaai/jina-reranker-v2-base-multilingual".to_string()
 }
@@ -86,10 +489,66 @@ fn default_reranker_max_sequence_length() -> usize {
     1024
 }
 
+fn default_max_request_body_bytes() -> usize {
+    2 * 1024 * 1024
+}
+
 fn default_max_batch() -> usize {
     8
 }
 
+fn default_lenient_content_type() -> bool {
+    true
+}
+
+fn default_adaptive_batching_target_latency_ms() -> u64 {
+    500
+}
+
+fn default_warmup_requests() -> usize {
+    1
+}
+
+fn default_triton_max_retries() -> u32 {
+    2
+}
+
+fn default_triton_retry_backoff_ms() -> u64 {
+    100
+}
+
+fn default_triton_binary_data() -> bool {
+    true
+}
+
+fn default_model_version_header() -> bool {
+    true
+}
+
+fn default_compression_min_bytes() -> u16 {
+    1024
+}
+
+fn default_error_rate_window_secs() -> u64 {
+    60
+}
+
+fn default_error_rate_min_requests() -> u64 {
+    20
+}
+
+fn default_server_host() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_server_port() -> u16 {
+    8000
+}
+
+fn default_shutdown_grace_secs() -> u64 {
+    30
+}
+
 fn default_api_title() -> String {
     "Jina AI API".to_string()
 }
@@ -102,6 +561,43 @@ fn default_api_version() -> String {
     "1.0.0".to_string()
 }
 
+/// Reads a boolean environment variable, accepting `true`, `1` and `yes`.
+fn env_flag(name: &str) -> Option<bool> {
+    std::env::var(name).ok().map(|value| {
+        let value = value.to_lowercase();
+        value == "true" || value == "1" || value == "yes"
+    })
+}
+
+/// Reads and parses an environment variable; unset or empty is `None`, and
+/// an unparseable value is an error naming the variable.
+fn env_parse<T>(name: &str) -> Result<Option<T>, config::ConfigError>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let Ok(value) = std::env::var(name) else {
+        return Ok(None);
+    };
+    if value.trim().is_empty() {
+        return Ok(None);
+    }
+    value.trim().parse().map(Some).map_err(|e| {
+        config::ConfigError::Message(format!("{} has an invalid value '{}': {}", name, value, e))
+    })
+}
+
+/// Reads a comma-separated environment variable, dropping empty entries.
+fn env_list(name: &str) -> Option<Vec<String>> {
+    std::env::var(name).ok().map(|value| {
+        value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+}
+
 impl Settings {
     pub fn new() -> Result<Self, config::ConfigError> {
         dotenv::dotenv().ok();
@@ -112,15 +608,225 @@ impl Settings {
 
         let mut settings: Settings = settings.try_deserialize()?;
         
+        settings.triton_fallback_url = std::env::var("TRITON_FALLBACK_URL").ok();
+
+        if let Ok(host) = std::env::var("SERVER_HOST") {
+            settings.server_host = host;
+        }
+        if let Ok(port) = std::env::var("SERVER_PORT") {
+            settings.server_port = port.trim().parse().map_err(|_| {
+                config::ConfigError::Message(format!("SERVER_PORT must be a port number (0-65535), got '{}'", port))
+            })?;
+        }
+
+        if let Some(secs) = env_parse("READINESS_POLL_SECS")? {
+            settings.readiness_poll_secs = Some(secs);
+        }
+        settings.health_cache_ttl_secs = env_parse("HEALTH_CACHE_TTL_SECS")?;
+        if let Some(secs) = env_parse("SHUTDOWN_GRACE_SECS")? {
+            settings.shutdown_grace_secs = secs;
+        }
+
+        if let Some(protocol) = env_parse("TRITON_PROTOCOL")? {
+            settings.triton_protocol = protocol;
+        }
+        if let Some(version) = env_parse("TRITON_HTTP_VERSION")? {
+            settings.triton_http_version = version;
+        }
+        if let Some(requests) = env_parse("WARMUP_REQUESTS")? {
+            settings.warmup_requests = requests;
+        }
+        if let Some(retries) = env_parse("TRITON_MAX_RETRIES")? {
+            settings.triton_max_retries = retries;
+        }
+        if let Some(backoff) = env_parse("TRITON_RETRY_BACKOFF_MS")? {
+            settings.triton_retry_backoff_ms = backoff;
+        }
+        if let Some(binary) = env_flag("TRITON_BINARY_DATA") {
+            settings.triton_binary_data = binary;
+        }
+        if let Ok(url) = std::env::var("TRITON_GRPC_URL") {
+            settings.triton_grpc_url = url;
+        }
+
+        if let Some(fatal) = env_flag("TRITON_STARTUP_PROBE_FATAL") {
+            settings.triton_startup_probe_fatal = fatal;
+        }
+
+        if let Some(fatal) = env_flag("OUTPUT_NAME_CHECK_FATAL") {
+            settings.output_name_check_fatal = fatal;
+        }
+
+        if let Some(headers) = env_list("TRITON_EXTRA_HEADERS") {
+            settings.triton_extra_headers = headers
+                .iter()
+                .map(|pair| match pair.split_once('=') {
+                    Some((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+                    None => (pair.clone(), String::new()),
+                })
+                .collect();
+        }
+
+        if let Some(routes) = env_list("EMBEDDING_MODEL_ROUTES") {
+            settings.embedding_model_routes = routes
+                .iter()
+                .map(|pair| match pair.split_once('=') {
+                    Some((name, model_name)) => (name.trim().to_string(), model_name.trim().to_string()),
+                    None => (pair.clone(), pair.clone()),
+                })
+                .collect();
+        }
+
+        if let Some(keys) = env_list("API_KEYS") {
+            settings.api_keys = keys
+                .iter()
+                .enumerate()
+                .map(|(position, entry)| match entry.split_once('=') {
+                    Some((label, key)) => (label.trim().to_string(), key.trim().to_string()),
+                    None => (format!("key-{}", position + 1), entry.clone()),
+                })
+                .collect();
+        }
+
+        if let Some(precheck) = env_flag("PRECHECK_READINESS") {
+            settings.precheck_readiness = precheck;
+        }
+
+        if let Ok(name) = std::env::var("EMBEDDING_OUTPUT_NAME") {
+            settings.embedding_output_name = name;
+        }
+        if let Ok(name) = std::env::var("RERANKER_OUTPUT_NAME") {
+            settings.reranker_output_name = name;
+        }
+
+        if let Ok(path) = std::env::var("TOKENIZER_PATH") {
+            settings.tokenizer_path = path;
+        }
+        if let Ok(path) = std::env::var("RERANKER_TOKENIZER_PATH") {
+            settings.reranker_tokenizer_path = path;
+        }
         settings.tokenizer_file = std::env::var("TOKENIZER_FILE").ok();
         settings.reranker_tokenizer_file = std::env::var("RERANKER_TOKENIZER_FILE").ok();
+        if let Ok(dir) = std::env::var("TOKENIZER_CACHE_DIR") {
+            settings.tokenizer_cache_dir = dir;
+        }
         
         settings.api_key = std::env::var("API_KEY").ok();
         
-        if let Ok(require_str) = std::env::var("REQUIRE_API_KEY") {
-            settings.require_api_key = require_str.to_lowercase() == "true" 
-                || require_str == "1" 
-                || require_str.to_lowercase() == "yes";
+        if let Some(require) = env_flag("REQUIRE_API_KEY") {
+            settings.require_api_key = require;
+        }
+
+        if let Some(enabled) = env_flag("ENABLE_ADMIN_ROUTES") {
+            settings.enable_admin_routes = enabled;
+        }
+
+        if let Some(enabled) = env_flag("MODEL_VERSION_HEADER") {
+            settings.model_version_header = enabled;
+        }
+
+        settings.error_rate_unhealthy_threshold = env_parse("ERROR_RATE_UNHEALTHY_THRESHOLD")?;
+        if let Some(window) = env_parse("ERROR_RATE_WINDOW_SECS")? {
+            settings.error_rate_window_secs = window;
+        }
+        if let Some(min_requests) = env_parse("ERROR_RATE_MIN_REQUESTS")? {
+            settings.error_rate_min_requests = min_requests;
+        }
+
+        settings.enabled_tasks = env_list("ENABLED_TASKS");
+        if let Ok(field) = std::env::var("DOCUMENT_TEXT_FIELD") {
+            settings.document_text_field = field;
+        }
+        settings.cors_allowed_origins = env_list("CORS_ALLOWED_ORIGINS");
+        settings.fallback_embedding_dim = env_parse("FALLBACK_EMBEDDING_DIM")?;
+        settings.expected_embedding_dim = env_parse("EXPECTED_EMBEDDING_DIM")?;
+        if let Some(fatal) = env_flag("CONSISTENCY_CHECK_FATAL") {
+            settings.consistency_check_fatal = fatal;
+        }
+        settings.projection_matrix_path = std::env::var("PROJECTION_MATRIX_PATH").ok();
+        settings.fixed_sequence_length = env_parse("FIXED_SEQUENCE_LENGTH")?;
+        settings.max_inputs_per_request = env_parse("MAX_INPUTS_PER_REQUEST")?;
+        settings.max_batch_items = env_parse("MAX_BATCH_ITEMS")?;
+        settings.max_concurrent_rerank_documents = env_parse("MAX_CONCURRENT_RERANK_DOCUMENTS")?;
+        if let Some(bytes) = env_parse("MAX_REQUEST_BODY_BYTES")? {
+            settings.max_request_body_bytes = bytes;
+        }
+        settings.max_response_items = env_parse("MAX_RESPONSE_ITEMS")?;
+        settings.max_batch_tokens = env_parse("MAX_BATCH_TOKENS")?;
+        if let Some(sort) = env_flag("SORT_BATCHES_BY_LENGTH") {
+            settings.sort_batches_by_length = sort;
+        }
+        if let Some(max_batch) = env_parse("RERANKER_CLIENT_MAX_BATCH")? {
+            settings.reranker_client_max_batch = max_batch;
+        }
+        if let Some(handling) = env_parse("RERANKER_SEPARATOR_HANDLING")? {
+            settings.reranker_separator_handling = handling;
+        }
+        settings.reranker_separator = std::env::var("RERANKER_SEPARATOR").ok();
+        settings.max_query_tokens = env_parse("MAX_QUERY_TOKENS")?;
+        if let Some(overflow) = env_parse("QUERY_OVERFLOW")? {
+            settings.query_overflow = overflow;
+        }
+        settings.max_request_memory_bytes = env_parse("MAX_REQUEST_MEMORY_BYTES")?;
+        if let Some(size) = env_parse("EMBEDDING_CACHE_SIZE")? {
+            settings.embedding_cache_size = size;
+        }
+        if let Some(dedup) = env_flag("DEDUP_INPUTS") {
+            settings.dedup_inputs = dedup;
+        }
+
+        if let Some(handling) = env_parse("INVALID_UNICODE_HANDLING")? {
+            settings.invalid_unicode_handling = handling;
+        }
+        if let Some(handling) = env_parse("EMPTY_INPUT_VECTOR")? {
+            settings.empty_input_vector = handling;
+        }
+        if let Some(send) = env_flag("RERANKER_TOKEN_TYPE_IDS") {
+            settings.reranker_token_type_ids = send;
+        }
+        if let Some(allow) = env_flag("ALLOW_BLANK_INPUTS") {
+            settings.allow_blank_inputs = allow;
+        }
+        if let Some(strict) = env_flag("STRICT_INPUT_SHAPE") {
+            settings.strict_input_shape = strict;
+        }
+        if let Some(lenient) = env_flag("LENIENT_CONTENT_TYPE") {
+            settings.lenient_content_type = lenient;
+        }
+        if let Some(fixed) = env_flag("FIXED_DECIMAL_FLOATS") {
+            settings.fixed_decimal_floats = fixed;
+        }
+        if let Some(enabled) = env_flag("ENABLE_COMPRESSION") {
+            settings.enable_compression = enabled;
+        }
+        if let Some(min_bytes) = env_parse("COMPRESSION_MIN_BYTES")? {
+            settings.compression_min_bytes = min_bytes;
+        }
+        if let Some(sanitize) = env_flag("SANITIZE_OUTPUT") {
+            settings.sanitize_output = sanitize;
+        }
+
+        if let Some(adaptive) = env_flag("ADAPTIVE_BATCHING") {
+            settings.adaptive_batching = adaptive;
+        }
+        if let Some(target) = env_parse("ADAPTIVE_BATCHING_TARGET_LATENCY_MS")? {
+            settings.adaptive_batching_target_latency_ms = target;
+        }
+
+        if let Some(strip_bom) = env_flag("PREPROCESS_STRIP_BOM") {
+            settings.preprocess_strip_bom = strip_bom;
+        }
+        if let Some(trim) = env_flag("PREPROCESS_TRIM_TRAILING_WHITESPACE") {
+            settings.preprocess_trim_trailing_whitespace = trim;
+        }
+        if let Some(lowercase) = env_flag("PREPROCESS_LOWERCASE") {
+            settings.preprocess_lowercase = lowercase;
+        }
+        if let Some(collapse) = env_flag("PREPROCESS_COLLAPSE_WHITESPACE") {
+            settings.preprocess_collapse_whitespace = collapse;
+        }
+        if let Some(strip) = env_flag("PREPROCESS_STRIP") {
+            settings.preprocess_strip = strip;
         }
         
         Ok(settings)
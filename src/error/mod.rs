@@ -25,6 +25,12 @@ pub enum AppError {
 
     #[error("Not ready: {0}")]
     NotReady(String),
+
+    #[error("Overloaded: {0}")]
+    Overloaded(String),
+
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
 }
 
 impl IntoResponse for AppError {
@@ -36,6 +42,8 @@ impl IntoResponse for AppError {
             AppError::Tokenization(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             AppError::NotReady(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
+            AppError::Overloaded(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg),
+            AppError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg),
         };
 
         let body = Json(json!({
@@ -0,0 +1,30 @@
+//! Hugging Face Hub HTTP setup shared by the server, which downloads
+//! tokenizers, and the `download_models` binary.
+
+use reqwest::header::{HeaderMap, HeaderValue, InvalidHeaderValue, AUTHORIZATION};
+use std::time::Duration;
+
+pub const HF_BASE_URL: &str = "https://huggingface.co";
+
+/// Time allowed to connect to the Hub; whole-request limits are up to callers
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// `Authorization: Bearer $HF_TOKEN` when the token is set, for gated and
+/// private repositories.
+fn auth_headers() -> Result<HeaderMap, InvalidHeaderValue> {
+    let mut headers = HeaderMap::new();
+    if let Some(token) = std::env::var("HF_TOKEN").ok().filter(|token| !token.is_empty()) {
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    Ok(headers)
+}
+
+/// Client builder with the connect timeout and `HF_TOKEN` authentication.
+/// Fails when `HF_TOKEN` is not a valid header value.
+pub fn client_builder() -> Result<reqwest::ClientBuilder, InvalidHeaderValue> {
+    Ok(reqwest::Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .default_headers(auth_headers()?))
+}
@@ -1,19 +1,26 @@
 mod api;
 mod config;
 mod error;
+mod hf_hub;
+mod metrics;
 mod middleware;
 mod models;
 mod repositories;
 mod services;
 
 use std::sync::Arc;
-use tracing::{info, error};
-use tracing_subscriber;
+use std::time::Duration;
+use tracing::{info, warn, error};
 
 use api::{create_router, health::AppState};
 use config::Settings;
+use error::AppError;
+use models::is_known_task;
+use config::TritonProtocol;
+use repositories::{build_repositories, triton_client::probe_reachability};
 use services::{
     embedding_service::EmbeddingService,
+    lifecycle::{self, Lifecycle},
     reranking_service::RerankingService,
     tokenizer_service::TokenizerService,
 };
@@ -34,9 +41,38 @@ async fn main() {
     info!("API Key configured: {}", settings.api_key.is_some());
     info!("Require API Key: {}", settings.require_api_key);
 
+    if let Some(enabled_tasks) = &settings.enabled_tasks {
+        if let Some(unknown) = enabled_tasks.iter().find(|task| !is_known_task(task)) {
+            error!("ENABLED_TASKS contains unknown task '{}'", unknown);
+            panic!("Cannot start with unknown task in ENABLED_TASKS: {}", unknown);
+        }
+        info!("Enabled tasks: {}", enabled_tasks.join(", "));
+    }
+
+    // Probe Triton so a wrong address shows up now rather than as 503s later
+    let probe_timeout = std::time::Duration::from_secs(settings.triton_http_connection_timeout);
+    let triton_addresses: Vec<&String> = match settings.triton_protocol {
+        TritonProtocol::Http => std::iter::once(&settings.triton_url)
+            .chain(settings.triton_fallback_url.iter().filter(|url| !url.is_empty()))
+            .collect(),
+        TritonProtocol::Grpc => vec![&settings.triton_grpc_url],
+    };
+    for address in triton_addresses {
+        info!("Probing Triton at {}...", address);
+        match probe_reachability(address, probe_timeout).await {
+            Ok(resolved) => info!("Triton at {} is reachable ({})", address, resolved),
+            Err(e) => {
+                error!("Triton is NOT reachable: {}. Check TRITON_URL / TRITON_FALLBACK_URL / TRITON_GRPC_URL.", e);
+                if settings.triton_startup_probe_fatal {
+                    panic!("Cannot start with unreachable Triton: {}", e);
+                }
+            }
+        }
+    }
+
     // Initialize tokenizers
     info!("Loading embedding tokenizer...");
-    match TokenizerService::load_embedding_tokenizer() {
+    match TokenizerService::load_embedding_tokenizer().await {
         Ok(_) => info!("Embedding tokenizer loaded successfully"),
         Err(e) => {
             error!("Failed to load embedding tokenizer: {:?}", e);
@@ -45,7 +81,7 @@ async fn main() {
     }
 
     info!("Loading reranker tokenizer...");
-    match TokenizerService::load_reranker_tokenizer() {
+    match TokenizerService::load_reranker_tokenizer().await {
         Ok(_) => info!("Reranker tokenizer loaded successfully"),
         Err(e) => {
             error!("Failed to load reranker tokenizer: {:?}", e);
@@ -53,9 +89,19 @@ async fn main() {
         }
     }
 
+    // One connection pool (HTTP) or channel (gRPC) shared by both Triton models
+    info!("Using Triton {:?} protocol", settings.triton_protocol);
+    let repositories = match build_repositories() {
+        Ok(repositories) => repositories,
+        Err(e) => {
+            error!("Failed to create Triton client: {:?}", e);
+            panic!("Cannot start without Triton client: {:?}", e);
+        }
+    };
+
     // Create services
     info!("Initializing embedding service...");
-    let embedding_service = match EmbeddingService::new() {
+    let embedding_service = match EmbeddingService::new(repositories.embedding, repositories.embedding_routes) {
         Ok(service) => {
             info!("Embedding service initialized");
             service
@@ -67,7 +113,7 @@ async fn main() {
     };
     
     info!("Initializing reranking service...");
-    let reranking_service = match RerankingService::new() {
+    let reranking_service = match RerankingService::new(repositories.reranking) {
         Ok(service) => {
             info!("Reranking service initialized");
             service
@@ -82,42 +128,110 @@ async fn main() {
     let state = Arc::new(AppState {
         embedding_service: Arc::new(embedding_service),
         reranking_service: Arc::new(reranking_service),
+        lifecycle: Arc::new(Lifecycle::new()),
     });
 
-    // Check if services are ready
-    info!("Checking service readiness...");
-    let embedding_ready = state.embedding_service.is_ready().await.unwrap_or(false);
-    let reranking_ready = state.reranking_service.is_ready().await.unwrap_or(false);
-    
-    if embedding_ready {
-        info!("Embedding service is ready");
-    } else {
-        info!("Warning: Embedding service is not ready");
-    }
-    
-    if reranking_ready {
-        info!("Reranking service is ready");
-    } else {
-        info!("Warning: Reranking service is not ready");
+    // Verify the output tensors we read exist in the deployed models
+    let output_checks = [
+        ("embedding", state.embedding_service.check_output_tensor().await),
+        ("reranking", state.reranking_service.check_output_tensor().await),
+    ];
+    for (service, result) in output_checks {
+        match result {
+            Ok(()) => info!("{} model output tensor verified", service),
+            Err(AppError::TritonConnection(e)) => {
+                warn!("Could not verify {} model output tensor: {}", service, e);
+            }
+            Err(e) => {
+                error!("{} model output tensor check failed: {}", service, e);
+                if settings.output_name_check_fatal {
+                    panic!("Cannot start with a misconfigured {} output tensor: {}", service, e);
+                }
+            }
+        }
     }
 
+    // Report live right away; readiness follows Triton and warmup in the background
+    tokio::spawn(lifecycle::monitor(
+        state.lifecycle.clone(),
+        state.embedding_service.clone(),
+        state.reranking_service.clone(),
+    ));
+
     // Create router
     let app = create_router(state);
 
     // Start server
-    let addr = "0.0.0.0:8000";
-    let listener = tokio::net::TcpListener::bind(addr)
-        .await
-        .expect("Failed to bind to address");
+    let addr = (settings.server_host.as_str(), settings.server_port);
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!("Failed to bind to {}:{}: {}. Check SERVER_HOST / SERVER_PORT.",
+                   settings.server_host, settings.server_port, e);
+            panic!("Cannot start without a listening socket: {}", e);
+        }
+    };
+    let local_addr = listener.local_addr().expect("Bound listener has a local address");
 
     info!("{} v{} listening on http://{}", 
         settings.api_title,
         settings.api_version,
-        addr
+        local_addr
     );
 
-    axum::serve(listener, app)
-        .await
-        .expect("Failed to start server");
+    // Stop accepting connections on the first signal, then give in-flight
+    // requests up to the grace period to finish
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("Received shutdown signal, shutting down (grace period {}s)", settings.shutdown_grace_secs);
+        let _ = shutdown_tx.send(true);
+    });
+
+    let mut graceful_rx = shutdown_rx.clone();
+    let server = axum::serve(listener, app).with_graceful_shutdown(async move {
+        let _ = graceful_rx.changed().await;
+    });
+
+    let mut deadline_rx = shutdown_rx;
+    let deadline = async move {
+        let _ = deadline_rx.changed().await;
+        tokio::time::sleep(Duration::from_secs(settings.shutdown_grace_secs)).await;
+    };
+
+    tokio::select! {
+        result = server => {
+            result.expect("Failed to start server");
+            info!("All in-flight requests finished, exiting");
+        }
+        _ = deadline => {
+            warn!("Requests still in flight after {}s grace period, exiting anyway", settings.shutdown_grace_secs);
+        }
+    }
+}
+
+/// Completes on Ctrl-C or, on Unix, SIGTERM (what Kubernetes sends on pod stop).
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
 
@@ -0,0 +1,309 @@
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::error::AppError;
+
+/// Upper bounds of the sequence length histogram buckets, in tokens
+const SEQUENCE_LENGTH_BUCKETS: &[u64] = &[16, 32, 64, 128, 256, 512, 1024, 2048, 4096, 8192];
+
+/// Upper bounds of the latency histogram buckets, in milliseconds
+const LATENCY_BUCKETS_MS: &[u64] = &[5, 10, 25, 50, 100, 250, 500, 1000, 2500, 5000, 10000, 30000];
+
+/// Process-wide request counters
+pub struct Metrics {
+    requests: ErrorRateWindow,
+    /// `/v1/embeddings` requests, errors and end-to-end latency
+    pub embedding_requests: RequestMetrics,
+    /// `/v1/rerank` requests, errors and end-to-end latency
+    pub rerank_requests: RequestMetrics,
+    /// Latency of each embedding inference call to Triton, in milliseconds
+    pub embedding_triton_latency: Histogram,
+    /// Latency of each reranker inference call to Triton, in milliseconds
+    pub reranker_triton_latency: Histogram,
+    /// Padded sequence length of each embedding batch sent to Triton
+    pub embedding_sequence_length: Histogram,
+    /// Padded sequence length of each reranker batch sent to Triton
+    pub reranker_sequence_length: Histogram,
+    /// Rerank documents currently holding `max_concurrent_rerank_documents` permits
+    pub rerank_documents_reserved: AtomicU64,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| Metrics {
+    requests: ErrorRateWindow::new(),
+    embedding_requests: RequestMetrics::new(),
+    rerank_requests: RequestMetrics::new(),
+    embedding_triton_latency: Histogram::new(LATENCY_BUCKETS_MS),
+    reranker_triton_latency: Histogram::new(LATENCY_BUCKETS_MS),
+    embedding_sequence_length: Histogram::new(SEQUENCE_LENGTH_BUCKETS),
+    reranker_sequence_length: Histogram::new(SEQUENCE_LENGTH_BUCKETS),
+    rerank_documents_reserved: AtomicU64::new(0),
+});
+
+impl Metrics {
+    pub fn get() -> &'static Metrics {
+        &METRICS
+    }
+
+    /// Records the outcome of an API request. Only server-side failures
+    /// (5xx other than 503, which signals unavailability or load shedding)
+    /// count as errors.
+    pub fn record_request(&self, status: StatusCode) {
+        let failed = status.is_server_error() && status != StatusCode::SERVICE_UNAVAILABLE;
+        self.requests.record(failed);
+    }
+
+    /// Error rate and request count over the last `window_secs` seconds.
+    pub fn recent_error_rate(&self, window_secs: u64) -> (f64, u64) {
+        self.requests.error_rate(window_secs)
+    }
+
+    /// All metrics in the Prometheus text exposition format.
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        self.embedding_requests.write_prometheus(&mut out, "embedding", "Embedding");
+        self.rerank_requests.write_prometheus(&mut out, "rerank", "Rerank");
+        write_histogram(
+            &mut out,
+            "triton_embedding_inference_duration_seconds",
+            "Latency of embedding inference calls to Triton",
+            &self.embedding_triton_latency.snapshot(),
+            MS_TO_SECONDS,
+        );
+        write_histogram(
+            &mut out,
+            "triton_reranker_inference_duration_seconds",
+            "Latency of reranker inference calls to Triton",
+            &self.reranker_triton_latency.snapshot(),
+            MS_TO_SECONDS,
+        );
+        write_histogram(
+            &mut out,
+            "embedding_batch_sequence_length_tokens",
+            "Padded sequence length of embedding batches sent to Triton",
+            &self.embedding_sequence_length.snapshot(),
+            1.0,
+        );
+        write_histogram(
+            &mut out,
+            "reranker_batch_sequence_length_tokens",
+            "Padded sequence length of reranker batches sent to Triton",
+            &self.reranker_sequence_length.snapshot(),
+            1.0,
+        );
+        write_gauge(
+            &mut out,
+            "rerank_documents_reserved",
+            "Rerank documents currently being scored under max_concurrent_rerank_documents",
+            self.rerank_documents_reserved.load(Ordering::Relaxed),
+        );
+
+        out
+    }
+}
+
+const MS_TO_SECONDS: f64 = 0.001;
+
+/// Request and error counts plus end-to-end latency for one endpoint
+pub struct RequestMetrics {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    latency: Histogram,
+}
+
+impl RequestMetrics {
+    fn new() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            latency: Histogram::new(LATENCY_BUCKETS_MS),
+        }
+    }
+
+    /// Runs a request handler, counting it (and any error) and timing it.
+    pub async fn track<T>(&self, handler: impl Future<Output = Result<T, AppError>>) -> Result<T, AppError> {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let started = Instant::now();
+
+        let result = handler.await;
+
+        self.latency.observe(started.elapsed().as_millis() as u64);
+        if result.is_err() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn write_prometheus(&self, out: &mut String, prefix: &str, label: &str) {
+        write_counter(
+            out,
+            &format!("{}_requests_total", prefix),
+            &format!("{} requests received", label),
+            self.requests.load(Ordering::Relaxed),
+        );
+        write_counter(
+            out,
+            &format!("{}_request_errors_total", prefix),
+            &format!("{} requests that failed", label),
+            self.errors.load(Ordering::Relaxed),
+        );
+        write_histogram(
+            out,
+            &format!("{}_request_duration_seconds", prefix),
+            &format!("End-to-end latency of {} requests", prefix),
+            &self.latency.snapshot(),
+            MS_TO_SECONDS,
+        );
+    }
+}
+
+fn write_counter(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+fn write_gauge(out: &mut String, name: &str, help: &str, value: u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+/// Writes a histogram, multiplying bounds and sum by `scale` to convert units.
+fn write_histogram(out: &mut String, name: &str, help: &str, snapshot: &HistogramSnapshot, scale: f64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for bucket in &snapshot.buckets {
+        match bucket.le {
+            Some(le) => {
+                let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le as f64 * scale, bucket.count);
+            }
+            None => {
+                let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, bucket.count);
+            }
+        }
+    }
+    let _ = writeln!(out, "{}_sum {}", name, snapshot.sum as f64 * scale);
+    let _ = writeln!(out, "{}_count {}", name, snapshot.count);
+}
+
+/// Fixed-bucket histogram safe to update from concurrent requests
+pub struct Histogram {
+    bounds: &'static [u64],
+    /// One count per bound plus a final overflow bucket
+    counts: Vec<AtomicU64>,
+    sum: AtomicU64,
+}
+
+/// Point-in-time histogram values with cumulative bucket counts, as in
+/// Prometheus (`le` is the inclusive upper bound, `None` for +Inf)
+#[derive(Debug, Serialize)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<HistogramBucket>,
+    pub sum: u64,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistogramBucket {
+    pub le: Option<u64>,
+    pub count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: u64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < value);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = self.counts
+            .iter()
+            .enumerate()
+            .map(|(i, count)| {
+                cumulative += count.load(Ordering::Relaxed);
+                HistogramBucket { le: self.bounds.get(i).copied(), count: cumulative }
+            })
+            .collect();
+
+        HistogramSnapshot {
+            buckets,
+            sum: self.sum.load(Ordering::Relaxed),
+            count: cumulative,
+        }
+    }
+}
+
+/// Request and error counts in one-second buckets, pruned as they age out.
+struct ErrorRateWindow {
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+struct Bucket {
+    second: u64,
+    total: u64,
+    errors: u64,
+}
+
+/// Buckets older than this are dropped regardless of the queried window
+const MAX_WINDOW_SECS: u64 = 3600;
+
+impl ErrorRateWindow {
+    fn new() -> Self {
+        Self { buckets: Mutex::new(VecDeque::new()) }
+    }
+
+    fn record(&self, failed: bool) {
+        let now = now_secs();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        match buckets.back_mut() {
+            Some(bucket) if bucket.second == now => {
+                bucket.total += 1;
+                bucket.errors += u64::from(failed);
+            }
+            _ => buckets.push_back(Bucket { second: now, total: 1, errors: u64::from(failed) }),
+        }
+
+        while buckets.front().is_some_and(|b| b.second + MAX_WINDOW_SECS < now) {
+            buckets.pop_front();
+        }
+    }
+
+    fn error_rate(&self, window_secs: u64) -> (f64, u64) {
+        let since = now_secs().saturating_sub(window_secs);
+        let buckets = self.buckets.lock().unwrap();
+
+        let (total, errors) = buckets
+            .iter()
+            .rev()
+            .take_while(|b| b.second > since)
+            .fold((0, 0), |(total, errors), b| (total + b.total, errors + b.errors));
+
+        let rate = if total == 0 { 0.0 } else { errors as f64 / total as f64 };
+        (rate, total)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
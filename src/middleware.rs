@@ -1,16 +1,24 @@
 use axum::{
     extract::Request,
-    http::{HeaderMap, StatusCode},
+    http::{HeaderMap, HeaderValue, StatusCode},
     middleware::Next,
-    response::{IntoResponse, Response},
+    response::Response,
 };
-use tracing::{info, warn};
+use std::time::Instant;
+use tracing::{info, info_span, warn, Instrument};
 
+use crate::api::headers::REQUEST_ID_HEADER;
 use crate::config::Settings;
+use crate::metrics::Metrics;
+
+/// Label of the API key that authenticated a request. Added to the request
+/// extensions for handlers and to the response extensions for logging.
+#[derive(Debug, Clone)]
+pub struct ApiKeyLabel(pub String);
 
 pub async fn auth_middleware(
     headers: HeaderMap,
-    request: Request,
+    mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
     let settings = Settings::get();
@@ -19,36 +27,60 @@ pub async fn auth_middleware(
         return Ok(next.run(request).await);
     }
 
-    let expected_key = match &settings.api_key {
-        Some(key) if !key.is_empty() => key,
-        _ => {
-            warn!("REQUIRE_API_KEY is true but API_KEY is not configured");
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    };
+    let legacy_key = settings.api_key
+        .as_deref()
+        .filter(|key| !key.is_empty())
+        .map(|key| ("default", key));
+    let keys: Vec<(&str, &str)> = settings.api_keys
+        .iter()
+        .filter(|(_, key)| !key.is_empty())
+        .map(|(label, key)| (label.as_str(), key.as_str()))
+        .chain(legacy_key)
+        .collect();
+
+    if keys.is_empty() {
+        warn!("REQUIRE_API_KEY is true but neither API_KEY nor API_KEYS is configured");
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
 
     let auth_header = headers
         .get("Authorization")
         .and_then(|h| h.to_str().ok())
         .unwrap_or("");
 
-    let provided_key = if auth_header.starts_with("Bearer ") {
-        &auth_header[7..]
-    } else {
-        auth_header
-    };
+    let provided_key = auth_header
+        .strip_prefix("Bearer ")
+        .unwrap_or(auth_header);
 
     if provided_key.is_empty() {
         warn!("Missing API key in request");
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    if provided_key != expected_key {
+    // Compare against every key so timing doesn't reveal which one matched
+    let mut matched = None;
+    for (label, key) in keys {
+        if constant_time_eq(provided_key.as_bytes(), key.as_bytes()) {
+            matched = Some(label);
+        }
+    }
+
+    let Some(label) = matched else {
         warn!("Invalid API key provided");
         return Err(StatusCode::UNAUTHORIZED);
-    }
+    };
+
+    let label = ApiKeyLabel(label.to_string());
+    request.extensions_mut().insert(label.clone());
+    let mut response = next.run(request).await;
+    response.extensions_mut().insert(label);
+    Ok(response)
+}
 
-    Ok(next.run(request).await)
+/// Byte comparison whose running time depends only on the input lengths,
+/// not on where the inputs first differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
 }
 
 pub async fn logging_middleware(
@@ -59,17 +91,81 @@ pub async fn logging_middleware(
     let uri = request.uri().clone();
     let path = uri.path().to_string();
     
+    let started = Instant::now();
     let response = next.run(request).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
     
     let status = response.status();
     
-    info!(
-        "{} {} - {}",
-        method,
-        path,
-        status.as_u16()
-    );
+    // Streamed bodies are still being sent, so their latency is time to headers
+    match response.extensions().get::<ApiKeyLabel>() {
+        Some(ApiKeyLabel(consumer)) => info!(
+            latency_ms,
+            "{} {} - {} (key: {})",
+            method,
+            path,
+            status.as_u16(),
+            consumer
+        ),
+        None => info!(
+            latency_ms,
+            "{} {} - {}",
+            method,
+            path,
+            status.as_u16()
+        ),
+    }
     
     response
 }
 
+
+/// Longest client-supplied `x-request-id` that is reused rather than replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Tags each request with an id: the client's `x-request-id` when it is a
+/// short printable value, otherwise a fresh UUID. Everything logged while the
+/// request is handled, down to the Triton client, carries it as a span field,
+/// and it is echoed in the `x-request-id` response header.
+pub async fn request_id_middleware(
+    request: Request,
+    next: Next,
+) -> Response {
+    let request_id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.bytes().all(|b| b.is_ascii_graphic()))
+        .map_or_else(new_request_id, str::to_string);
+
+    let span = info_span!("request", request_id = %request_id);
+    let mut response = next.run(request).instrument(span).await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// Random (version 4) UUID in its hyphenated form.
+fn new_request_id() -> String {
+    let mut bytes = [0u8; 16];
+    if let Err(e) = getrandom::getrandom(&mut bytes) {
+        warn!("Failed to generate a random request id: {}", e);
+    }
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// Records API request outcomes for the error-rate health signal.
+pub async fn metrics_middleware(
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+    Metrics::get().record_request(response.status());
+    response
+}
@@ -1,6 +1,12 @@
-use serde::{Deserialize, Serialize};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde::{de, ser::SerializeSeq, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{value::RawValue, Value};
+use std::time::Duration;
 use utoipa::ToSchema;
 
+use crate::config::Settings;
+use crate::error::AppError;
+
 // Request/Response models
 /// Embedding request with support for different task types
 #[derive(Debug, Deserialize, ToSchema)]
@@ -11,15 +17,19 @@ use utoipa::ToSchema;
     "encoding_format": "float"
 }))]
 pub struct EmbeddingRequest {
-    /// Input text(s) to generate embeddings for
+    /// Input text(s) to generate embeddings for, or their token ids from the
+    /// embedding tokenizer, special tokens included
     #[schema(example = json!(["Xin chào, bạn khỏe không?", "Machine learning là gì?"]))]
     pub input: InputText,
-    #[serde(default = "default_model")]
-    #[schema(default = "jina-embeddings-v3")]
-    pub model: String,
-    #[serde(default = "default_encoding_format")]
+    /// Model to embed with: the configured embedding model or one of
+    /// `EMBEDDING_MODEL_ROUTES`. Omitted, it is the configured embedding
+    /// model. The response's `model` is the Triton model it resolved to.
+    #[schema(example = "jina-embeddings-v3")]
+    pub model: Option<String>,
+    /// Format of every embedding in the response: "float" or "base64".
+    /// Overrides the format negotiated from the `Accept` header.
     #[schema(default = "float")]
-    pub encoding_format: String,
+    pub encoding_format: Option<String>,
     /// Task type for LoRA adapter selection. Valid values:
     /// - "retrieval.query" (default): For search queries
     /// - "retrieval.passage": For document passages
@@ -29,41 +39,413 @@ pub struct EmbeddingRequest {
     #[serde(default = "default_task")]
     #[schema(default = "retrieval.query")]
     pub task: String,
+    /// Blend several tasks: each input is embedded once per task and the
+    /// weighted average of the normalized embeddings is returned.
+    /// Overrides `task` when set.
+    #[schema(example = json!([{"task": "retrieval.query", "weight": 0.7}, {"task": "classification", "weight": 0.3}]))]
+    pub tasks: Option<Vec<TaskWeight>>,
+    /// L2-normalize each embedding to unit length (default true)
+    #[schema(default = true)]
+    pub normalized: Option<bool>,
+    #[allow(dead_code)]
     pub user: Option<String>,
+    /// Set to false to omit the `usage` object from the response
+    #[serde(default = "default_include_usage")]
+    #[schema(default = true)]
+    pub include_usage: bool,
+    /// Include Triton output metadata and the padded shape of each Triton call
+    /// in the response (requires admin routes to be enabled)
+    #[serde(default)]
+    #[schema(default = false)]
+    pub debug: bool,
+    /// Report inputs that fail tokenization in `warnings` and embed the rest,
+    /// instead of failing the whole request
+    #[serde(default)]
+    #[schema(default = false)]
+    pub isolate_errors: bool,
+    /// Add each embedding's L2 norm before normalization as `norm`, so the
+    /// unnormalized vector is `embedding * norm`
+    #[serde(default)]
+    #[schema(default = false)]
+    pub return_norms: bool,
+    /// Echo the effective request parameters, defaults included, as `request_params`
+    #[serde(default)]
+    #[schema(default = false)]
+    pub echo_params: bool,
+    /// Bypass all caches and always run inference; the result is not cached.
+    /// Equivalent to sending `Cache-Control: no-store`.
+    #[serde(default)]
+    #[schema(default = false)]
+    pub no_cache: bool,
+    /// Upper bound in milliseconds on each Triton call for this request,
+    /// capped by `TRITON_HTTP_NETWORK_TIMEOUT`
+    #[schema(example = 2000, minimum = 1)]
+    pub timeout_ms: Option<u64>,
+    /// Which end of an input longer than `MAX_SEQUENCE_LENGTH` tokens is cut
+    #[serde(default)]
+    #[schema(default = "right")]
+    pub truncation: Truncation,
 }
 
-/// Input text can be a single string or an array of strings
-#[derive(Debug, Deserialize, ToSchema)]
+/// How inputs longer than the maximum sequence length are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Truncation {
+    /// Keep the first tokens
+    #[default]
+    Right,
+    /// Keep the last tokens, along with the leading special tokens
+    Left,
+    /// Reject over-length inputs
+    None,
+}
+
+/// One task of a weighted multi-task embedding
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TaskWeight {
+    pub task: String,
+    pub weight: f32,
+}
+
+/// Input text can be a single string or an array of strings, or their
+/// pre-tokenized forms: an array of token ids or an array of such arrays.
+///
+/// A string or a single token-id array yields a `data` array with one item
+/// at index 0; an array of inputs yields one item per input, each carrying
+/// its input's `index`.
+#[derive(Debug, ToSchema)]
 #[serde(untagged)]
 pub enum InputText {
     /// Single text input
     Single(String),
     /// Multiple text inputs for batch processing
     Multiple(Vec<String>),
+    /// Single input already tokenized with the embedding tokenizer
+    TokenIds(Vec<u32>),
+    /// Multiple inputs already tokenized with the embedding tokenizer
+    TokenIdBatch(Vec<Vec<u32>>),
+}
+
+impl<'de> Deserialize<'de> for InputText {
+    /// Picks the variant from the JSON shape, naming the shape in the error
+    /// when none fits instead of serde's generic untagged enum message.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Value::deserialize(deserializer)? {
+            Value::String(text) => Ok(InputText::Single(text)),
+            Value::Array(items) if items.iter().all(Value::is_string) => Ok(InputText::Multiple(
+                items
+                    .into_iter()
+                    .filter_map(|item| match item {
+                        Value::String(text) => Some(text),
+                        _ => None,
+                    })
+                    .collect(),
+            )),
+            Value::Array(items) if items.iter().all(Value::is_number) => {
+                token_id_list(&items).map(InputText::TokenIds).map_err(de::Error::custom)
+            }
+            Value::Array(items) if items.iter().all(is_number_array) => items
+                .iter()
+                .filter_map(Value::as_array)
+                .map(|ids| token_id_list(ids))
+                .collect::<Result<_, _>>()
+                .map(InputText::TokenIdBatch)
+                .map_err(de::Error::custom),
+            other => Err(de::Error::custom(input_shape_error(&other))),
+        }
+    }
+}
+
+fn is_number_array(value: &Value) -> bool {
+    value.as_array().is_some_and(|items| items.iter().all(Value::is_number))
+}
+
+fn token_id_list(items: &[Value]) -> Result<Vec<u32>, String> {
+    items
+        .iter()
+        .map(|item| {
+            item.as_u64()
+                .and_then(|id| u32::try_from(id).ok())
+                .ok_or_else(|| format!("token ids must be integers from 0 to {}, got {}", u32::MAX, item))
+        })
+        .collect()
+}
+
+fn input_shape_error(value: &Value) -> String {
+    let got = match value {
+        Value::Array(items) if items.iter().any(Value::is_array) => format!("nested array {}", json_shape(value)),
+        Value::Array(_) => format!("array {}", json_shape(value)),
+        other => json_shape(other),
+    };
+    format!(
+        "input must be a string, an array of strings, or token ids (an array of integers or of integer arrays), got {}",
+        got
+    )
+}
+
+/// Type outline of a JSON value, listing each distinct element shape of an
+/// array once, e.g. `[[string]]` for `[["a", "b"]]`.
+fn json_shape(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => "boolean".to_string(),
+        Value::Number(_) => "number".to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Object(_) => "object".to_string(),
+        Value::Array(items) => {
+            let mut shapes: Vec<String> = Vec::new();
+            for shape in items.iter().map(json_shape) {
+                if !shapes.contains(&shape) {
+                    shapes.push(shape);
+                }
+            }
+            format!("[{}]", shapes.join(", "))
+        }
+    }
+}
+
+/// Embedding inputs as texts to tokenize, or as token ids sent to the model as given
+#[derive(Debug)]
+pub enum EmbeddingInputs {
+    Texts(Vec<String>),
+    TokenIds(Vec<Vec<u32>>),
+}
+
+impl EmbeddingInputs {
+    pub fn len(&self) -> usize {
+        match self {
+            EmbeddingInputs::Texts(texts) => texts.len(),
+            EmbeddingInputs::TokenIds(ids) => ids.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 impl InputText {
-    pub fn to_vec(self) -> Vec<String> {
+    pub fn is_single(&self) -> bool {
+        matches!(self, InputText::Single(_) | InputText::TokenIds(_))
+    }
+
+    pub fn into_inputs(self) -> EmbeddingInputs {
         match self {
-            InputText::Single(s) => vec![s],
-            InputText::Multiple(v) => v,
+            InputText::Single(s) => EmbeddingInputs::Texts(vec![s]),
+            InputText::Multiple(v) => EmbeddingInputs::Texts(v),
+            InputText::TokenIds(ids) => EmbeddingInputs::TokenIds(vec![ids]),
+            InputText::TokenIdBatch(ids) => EmbeddingInputs::TokenIds(ids),
         }
     }
 }
 
 #[derive(Debug, Serialize, ToSchema)]
+#[schema(example = json!({
+    "object": "list",
+    "data": [
+        {"object": "embedding", "embedding": [0.0123, -0.0456, 0.0789], "index": 0}
+    ],
+    "model": "jina-embeddings-v3",
+    "created": 1760572800,
+    "usage": {"prompt_tokens": 9, "total_tokens": 9}
+}))]
 pub struct EmbeddingResponse {
     pub object: String,
     pub data: Vec<EmbeddingData>,
+    /// Triton model that produced the embeddings, which may differ from the
+    /// requested `model` when it was routed or not recognized
+    pub model: String,
+    /// Unix timestamp (seconds) of when the response was created
+    pub created: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<EmbeddingUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub debug: Option<EmbeddingDebug>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_params: Option<EmbeddingRequestParams>,
+    /// Indices of empty inputs left out of `data` (only with `EMPTY_INPUT_VECTOR=skip`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_indices: Vec<usize>,
+    /// Inputs left out of `data` because they failed (only with `isolate_errors`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<InputWarning>,
+}
+
+/// A single input that could not be embedded
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct InputWarning {
+    pub index: usize,
+    pub message: String,
+}
+
+/// Effective parameters of an embedding request, returned when `echo_params` is set
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmbeddingRequestParams {
     pub model: String,
-    pub usage: EmbeddingUsage,
+    pub task: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tasks: Option<Vec<TaskWeight>>,
+    pub encoding_format: String,
+    pub normalized: bool,
+    pub truncation: Truncation,
+    pub include_usage: bool,
+    pub input_count: usize,
+}
+
+/// Diagnostics returned only for debug requests
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmbeddingDebug {
+    /// Output tensor returned by each Triton call, in batch order
+    pub output_tensors: Vec<OutputTensorInfo>,
+    /// Padded input shape sent in each Triton call, in batch order
+    pub batch_shapes: Vec<BatchShape>,
+}
+
+/// Input shape of one Triton call, for judging padding overhead
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct BatchShape {
+    pub batch_size: usize,
+    /// Sequence length every input in the batch was padded to
+    pub padded_length: usize,
+    /// Real (non-padding) tokens in the batch
+    pub tokens: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OutputTensorInfo {
+    pub name: String,
+    pub datatype: String,
+    pub shape: Vec<usize>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct EmbeddingData {
     pub object: String,
-    pub embedding: Vec<f32>,
+    pub embedding: EmbeddingVector,
     pub index: usize,
+    /// L2 norm before normalization (present when `return_norms` is set)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub norm: Option<f32>,
+}
+
+/// Embedding payload, either a float array or base64 of little-endian f32 bytes
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum EmbeddingVector {
+    Float(#[serde(serialize_with = "serialize_floats")] Vec<f32>),
+    Base64(String),
+}
+
+/// Serializes floats with serde's standard formatting, or in fixed decimal
+/// notation when `fixed_decimal_floats` is enabled. Non-finite values become
+/// `null` either way.
+fn serialize_floats<S: Serializer>(vector: &[f32], serializer: S) -> Result<S::Ok, S::Error> {
+    if !Settings::get().fixed_decimal_floats {
+        return vector.serialize(serializer);
+    }
+
+    let mut seq = serializer.serialize_seq(Some(vector.len()))?;
+    for value in vector {
+        if value.is_finite() {
+            // `Display` for floats never uses an exponent
+            let number = RawValue::from_string(value.to_string()).map_err(serde::ser::Error::custom)?;
+            seq.serialize_element(&number)?;
+        } else {
+            seq.serialize_element(&None::<f32>)?;
+        }
+    }
+    seq.end()
+}
+
+/// Response encoding requested for embeddings. One format applies to every
+/// item of a request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EncodingFormat {
+    Float,
+    Base64,
+}
+
+impl EncodingFormat {
+    pub fn parse(format: &str) -> Result<Self, AppError> {
+        match format {
+            "float" => Ok(EncodingFormat::Float),
+            "base64" => Ok(EncodingFormat::Base64),
+            other => Err(AppError::Validation(format!(
+                "Invalid encoding_format '{}'. Valid formats: float, base64",
+                other
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EncodingFormat::Float => "float",
+            EncodingFormat::Base64 => "base64",
+        }
+    }
+
+    pub fn encode(self, vector: Vec<f32>) -> EmbeddingVector {
+        match self {
+            EncodingFormat::Float => EmbeddingVector::Float(vector),
+            EncodingFormat::Base64 => {
+                let bytes: Vec<u8> = vector.iter().flat_map(|v| v.to_le_bytes()).collect();
+                EmbeddingVector::Base64(BASE64.encode(bytes))
+            }
+        }
+    }
+}
+
+/// Request embedding a query and its passages in one call, each with the
+/// matching retrieval task
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "query": "Machine learning là gì?",
+    "passages": [
+        "Machine learning là một nhánh của trí tuệ nhân tạo.",
+        "Python là ngôn ngữ lập trình phổ biến."
+    ],
+    "model": "jina-embeddings-v3"
+}))]
+pub struct EmbeddingPairRequest {
+    /// Query text, embedded with the "retrieval.query" task
+    pub query: String,
+    /// Passage texts, embedded with the "retrieval.passage" task
+    pub passages: Vec<String>,
+    /// L2-normalize each embedding to unit length (default true)
+    #[schema(default = true)]
+    pub normalized: Option<bool>,
+    /// Model to embed with, as for `/v1/embeddings`
+    #[schema(example = "jina-embeddings-v3")]
+    pub model: Option<String>,
+    /// Format of every embedding in the response: "float" or "base64".
+    /// Overrides the format negotiated from the `Accept` header.
+    #[schema(default = "float")]
+    pub encoding_format: Option<String>,
+    /// Set to false to omit the `usage` object from the response
+    #[serde(default = "default_include_usage")]
+    #[schema(default = true)]
+    pub include_usage: bool,
+    /// Bypass all caches and always run inference; the result is not cached.
+    /// Equivalent to sending `Cache-Control: no-store`.
+    #[serde(default)]
+    #[schema(default = false)]
+    pub no_cache: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EmbeddingPairResponse {
+    pub object: String,
+    /// Query embedding (task "retrieval.query")
+    pub query: EmbeddingData,
+    /// Passage embeddings in input order (task "retrieval.passage")
+    pub passages: Vec<EmbeddingData>,
+    /// Indices of empty passages left out of `passages` (only with `EMPTY_INPUT_VECTOR=skip`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_indices: Vec<usize>,
+    /// Triton model that produced the embeddings, as for `/v1/embeddings`
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<EmbeddingUsage>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -93,11 +475,49 @@ pub struct RerankRequest {
     #[serde(default = "default_rerank_model")]
     #[schema(default = "jina-reranker-v2")]
     pub model: String,
-    #[schema(example = 2)]
+    /// Number of results to return; must be at least 1. Values above the
+    /// number of documents return every document.
+    #[schema(example = 2, minimum = 1)]
     pub top_n: Option<usize>,
+    /// Number of ranked results to skip, for paging through results with
+    /// `top_n` as the page size. Must be less than the number of documents.
+    #[schema(example = 0)]
+    pub offset: Option<usize>,
     #[serde(default = "default_return_documents")]
     #[schema(default = true)]
     pub return_documents: bool,
+    /// Key holding the text to rerank in object documents. Defaults to the
+    /// `DOCUMENT_TEXT_FIELD` setting (`text`); objects without it are
+    /// reranked as serialized JSON.
+    #[schema(example = "text")]
+    pub document_text_field: Option<String>,
+    /// How relevance scores are reported. `softmax` is computed over the
+    /// returned results only, so scores change whenever the candidate set
+    /// (documents or `top_n`) changes.
+    #[serde(default)]
+    #[schema(default = "raw")]
+    pub score_format: ScoreFormat,
+    /// Set to false to omit the `usage` object from the response
+    #[serde(default = "default_include_usage")]
+    #[schema(default = true)]
+    pub include_usage: bool,
+    /// Upper bound in milliseconds on each Triton call for this request,
+    /// capped by `TRITON_HTTP_NETWORK_TIMEOUT`
+    #[schema(example = 2000, minimum = 1)]
+    pub timeout_ms: Option<u64>,
+}
+
+/// Output format for rerank relevance scores
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ScoreFormat {
+    /// Raw model logits
+    #[default]
+    Raw,
+    /// Independent per-document probabilities
+    Sigmoid,
+    /// Distribution summing to 1 across the returned results
+    Softmax,
 }
 
 #[derive(Debug, Deserialize, ToSchema)]
@@ -108,10 +528,28 @@ pub enum DocumentInput {
 }
 
 impl DocumentInput {
-    pub fn as_text(&self) -> String {
+    /// Text to rerank. For objects this is the string under `text_field` when
+    /// the object has one, otherwise the whole object serialized as JSON. A
+    /// non-string value under `text_field` is rejected.
+    pub fn as_text(&self, index: usize, text_field: Option<&str>) -> Result<String, AppError> {
+        match self {
+            DocumentInput::Text(s) => Ok(s.clone()),
+            DocumentInput::Object(v) => match text_field.and_then(|field| Some((field, v.get(field)?))) {
+                Some((_, serde_json::Value::String(text))) => Ok(text.clone()),
+                Some((field, _)) => Err(AppError::Validation(format!(
+                    "Document {} has a non-string '{}' field; it must hold the text to rerank",
+                    index, field
+                ))),
+                None => Ok(v.to_string()),
+            },
+        }
+    }
+
+    /// The document as sent in the request, for echoing back in results
+    pub fn to_value(&self) -> serde_json::Value {
         match self {
-            DocumentInput::Text(s) => s.clone(),
-            DocumentInput::Object(v) => v.to_string(),
+            DocumentInput::Text(s) => serde_json::Value::String(s.clone()),
+            DocumentInput::Object(v) => v.clone(),
         }
     }
 }
@@ -121,15 +559,17 @@ pub struct RerankResponse {
     pub object: String,
     pub data: Vec<RerankResult>,
     pub model: String,
-    pub usage: RerankUsage,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<RerankUsage>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
 pub struct RerankResult {
     pub index: usize,
     pub relevance_score: f32,
+    /// The document as sent: a string, or the full object
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub document: Option<String>,
+    pub document: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -137,11 +577,218 @@ pub struct RerankUsage {
     pub total_tokens: usize,
 }
 
+// Two-stage retrieval models
+/// Query and candidate documents for embedding-similarity filtering followed
+/// by reranking. At least one stage must be enabled.
+#[derive(Debug, Deserialize, ToSchema)]
+#[schema(example = json!({
+    "query": "Machine learning là gì?",
+    "documents": [
+        "Machine learning là một nhánh của trí tuệ nhân tạo.",
+        "Python là ngôn ngữ lập trình phổ biến.",
+        "Deep learning sử dụng mạng neural nhiều lớp."
+    ],
+    "candidate_count": 2,
+    "top_n": 1
+}))]
+pub struct RetrieveRequest {
+    pub query: String,
+    pub documents: Vec<String>,
+    /// Stage 1: score documents by cosine similarity of their embeddings to
+    /// the query embedding and keep the `candidate_count` best
+    #[serde(default = "default_stage_enabled")]
+    #[schema(default = true)]
+    pub embedding_filter: bool,
+    /// Documents passed from the similarity stage to the reranker
+    #[serde(default = "default_candidate_count")]
+    #[schema(default = 50)]
+    pub candidate_count: usize,
+    /// Stage 2: score the remaining documents with the reranker model
+    #[serde(default = "default_stage_enabled")]
+    #[schema(default = true)]
+    pub rerank: bool,
+    /// Number of final results to return (all survivors by default)
+    pub top_n: Option<usize>,
+    #[serde(default = "default_return_documents")]
+    #[schema(default = true)]
+    pub return_documents: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RetrieveResponse {
+    pub object: String,
+    /// Results ordered by rerank score, or by similarity when reranking is disabled
+    pub data: Vec<RetrieveResult>,
+    pub embedding_model: String,
+    pub reranker_model: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RetrieveResult {
+    /// Index of the document in the request
+    pub index: usize,
+    /// Cosine similarity to the query (present when `embedding_filter` is enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similarity_score: Option<f32>,
+    /// Reranker relevance score (present when `rerank` is enabled)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rerank_score: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<String>,
+}
+
 // Domain models
 #[derive(Debug, Clone)]
 pub struct EmbeddingModel {
     pub vector: Vec<f32>,
     pub index: usize,
+    pub norm: Option<f32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmbeddingOutput {
+    pub embeddings: Vec<EmbeddingModel>,
+    pub output_tensors: Vec<OutputTensorInfo>,
+    pub batch_shapes: Vec<BatchShape>,
+    /// Indices of empty inputs omitted under `empty_input_vector = skip`
+    pub skipped_indices: Vec<usize>,
+    /// Real (non-padding) tokens across all inputs
+    pub prompt_tokens: usize,
+    /// Inputs omitted because they failed under `isolate_errors`
+    pub failed_inputs: Vec<InputWarning>,
+}
+
+/// Embeddings for one request in a single row-major buffer: row `i` is
+/// `data[i * dim..(i + 1) * dim]` and belongs to input `indices[i]`.
+#[derive(Debug, Clone)]
+pub struct FlatEmbeddings {
+    pub data: Vec<f32>,
+    /// `(rows, dim)`
+    pub shape: (usize, usize),
+    pub indices: Vec<usize>,
+    /// Per-row L2 norm before normalization, when requested
+    pub norms: Option<Vec<f32>>,
+}
+
+impl FlatEmbeddings {
+    /// Adds a zero row for each input in `empty`, keeping rows ordered by
+    /// input index. Both `empty` and the existing indices must be ascending.
+    pub fn insert_zero_rows(&mut self, empty: &[usize]) {
+        let dim = self.shape.1;
+        let rows = self.indices.len() + empty.len();
+        let mut data = Vec::with_capacity(rows * dim);
+        let mut indices = Vec::with_capacity(rows);
+        let mut norms = self.norms.as_ref().map(|_| Vec::with_capacity(rows));
+        let old_norms = self.norms.take().unwrap_or_default();
+        let mut existing = self.indices.iter().zip(self.data.chunks(dim.max(1))).enumerate();
+        let mut next = existing.next();
+
+        for &index in empty {
+            while let Some((row, (&row_index, vector))) = next.filter(|(_, (&row_index, _))| row_index < index) {
+                indices.push(row_index);
+                data.extend_from_slice(vector);
+                if let Some(norms) = &mut norms {
+                    norms.push(old_norms[row]);
+                }
+                next = existing.next();
+            }
+            indices.push(index);
+            data.resize(data.len() + dim, 0.0);
+            if let Some(norms) = &mut norms {
+                norms.push(0.0);
+            }
+        }
+        while let Some((row, (&row_index, vector))) = next {
+            indices.push(row_index);
+            data.extend_from_slice(vector);
+            if let Some(norms) = &mut norms {
+                norms.push(old_norms[row]);
+            }
+            next = existing.next();
+        }
+
+        self.data = data;
+        self.indices = indices;
+        self.norms = norms;
+        self.shape.0 = rows;
+    }
+}
+
+/// `EmbeddingOutput` with the vectors kept in one contiguous buffer
+#[derive(Debug, Clone)]
+pub struct FlatEmbeddingOutput {
+    pub embeddings: FlatEmbeddings,
+    pub output_tensors: Vec<OutputTensorInfo>,
+    pub batch_shapes: Vec<BatchShape>,
+    pub skipped_indices: Vec<usize>,
+    pub prompt_tokens: usize,
+    pub failed_inputs: Vec<InputWarning>,
+}
+
+impl From<FlatEmbeddingOutput> for EmbeddingOutput {
+    fn from(output: FlatEmbeddingOutput) -> Self {
+        let FlatEmbeddings { data, shape: (_, dim), indices, norms } = output.embeddings;
+        let mut norms = norms.map(Vec::into_iter);
+        let embeddings = indices
+            .into_iter()
+            .enumerate()
+            .map(|(row, index)| EmbeddingModel {
+                vector: data[row * dim..(row + 1) * dim].to_vec(),
+                index,
+                norm: norms.as_mut().and_then(Iterator::next),
+            })
+            .collect();
+
+        Self {
+            embeddings,
+            output_tensors: output.output_tensors,
+            batch_shapes: output.batch_shapes,
+            skipped_indices: output.skipped_indices,
+            prompt_tokens: output.prompt_tokens,
+            failed_inputs: output.failed_inputs,
+        }
+    }
+}
+
+/// Per-request embedding behavior
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddingOptions {
+    /// L2-normalize each vector
+    pub normalize: bool,
+    /// Skip inputs that fail tokenization instead of failing the request
+    pub isolate_errors: bool,
+    /// Record each vector's L2 norm before normalization
+    pub return_norms: bool,
+    /// Bound on each Triton call
+    pub timeout: Option<Duration>,
+    /// Handling of over-length inputs
+    pub truncation: Truncation,
+    /// Neither read nor fill the embedding cache
+    pub no_cache: bool,
+}
+
+impl Default for EmbeddingOptions {
+    fn default() -> Self {
+        Self {
+            normalize: true,
+            isolate_errors: false,
+            return_norms: false,
+            timeout: None,
+            truncation: Truncation::Right,
+            no_cache: false,
+        }
+    }
+}
+
+/// Per-request reranking behavior
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RerankOptions {
+    pub top_n: Option<usize>,
+    pub offset: Option<usize>,
+    pub return_documents: bool,
+    pub score_format: ScoreFormat,
+    /// Bound on each Triton call
+    pub timeout: Option<Duration>,
 }
 
 #[derive(Debug, Clone)]
@@ -151,19 +798,19 @@ pub struct RerankModel {
     pub document: Option<String>,
 }
 
-// Default functions
-fn default_model() -> String {
-    "jina-embeddings-v3".to_string()
+#[derive(Debug, Clone)]
+pub struct RerankOutput {
+    pub results: Vec<RerankModel>,
+    /// Real (non-padding) tokens across all query/document pairs, after
+    /// truncation
+    pub total_tokens: usize,
 }
 
+// Default functions
 fn default_rerank_model() -> String {
     "jina-reranker-v2".to_string()
 }
 
-fn default_encoding_format() -> String {
-    "float".to_string()
-}
-
 fn default_task() -> String {
     "retrieval.query".to_string()
 }
@@ -172,6 +819,18 @@ fn default_return_documents() -> bool {
     true
 }
 
+fn default_include_usage() -> bool {
+    true
+}
+
+fn default_stage_enabled() -> bool {
+    true
+}
+
+fn default_candidate_count() -> usize {
+    50
+}
+
 // Task mapping constants
 pub const TASK_MAPPING: &[(&str, i64)] = &[
     ("retrieval.query", 0),
@@ -181,10 +840,23 @@ pub const TASK_MAPPING: &[(&str, i64)] = &[
     ("text-matching", 4),
 ];
 
-pub fn get_task_id(task: &str) -> i64 {
+pub fn is_known_task(task: &str) -> bool {
+    TASK_MAPPING.iter().any(|(t, _)| *t == task)
+}
+
+/// Task id for a task name, rejecting unknown names. Requests that omit
+/// `task` get "retrieval.query" through the serde default instead.
+pub fn get_task_id_checked(task: &str) -> Result<i64, AppError> {
     TASK_MAPPING
         .iter()
         .find(|(t, _)| *t == task)
         .map(|(_, id)| *id)
-        .unwrap_or(0)
+        .ok_or_else(|| {
+            let valid: Vec<&str> = TASK_MAPPING.iter().map(|(t, _)| *t).collect();
+            AppError::Validation(format!(
+                "Unknown task '{}'. Valid tasks: {}",
+                task,
+                valid.join(", ")
+            ))
+        })
 }
@@ -1,27 +1,121 @@
+pub mod retry;
 pub mod triton_client;
+pub mod triton_grpc;
 
+use crate::config::{Settings, TritonProtocol};
 use crate::error::AppError;
+use crate::models::OutputTensorInfo;
 use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::Duration;
+use triton_client::{build_http_client, TritonClient};
+use triton_grpc::{build_grpc_channel, TritonGrpcClient};
 
 #[async_trait]
 pub trait EmbeddingRepository: Send + Sync {
+    /// Row-major embeddings for the batch, with the embedding dimension.
+    /// `timeout` bounds the call when the request sets one.
     async fn generate_embeddings(
         &self,
         input_ids: &[Vec<i64>],
         attention_mask: &[Vec<i64>],
         task_id: i64,
-    ) -> Result<Vec<Vec<f32>>, AppError>;
+        timeout: Option<Duration>,
+    ) -> Result<(Vec<f32>, usize, OutputTensorInfo), AppError>;
 
     async fn is_ready(&self) -> Result<bool, AppError>;
+
+    async fn get_model_version(&self) -> Result<Option<String>, AppError>;
+
+    async fn check_output_name(&self) -> Result<(), AppError>;
 }
 
 #[async_trait]
 pub trait RerankingRepository: Send + Sync {
+    /// Scores a batch of query/document pairs. `token_type_ids` is sent as a
+    /// third input only when given.
     async fn generate_scores(
         &self,
         input_ids: &[Vec<i64>],
         attention_mask: &[Vec<i64>],
+        token_type_ids: Option<&[Vec<i64>]>,
+        timeout: Option<Duration>,
     ) -> Result<Vec<f32>, AppError>;
 
     async fn is_ready(&self) -> Result<bool, AppError>;
+
+    async fn get_model_version(&self) -> Result<Option<String>, AppError>;
+
+    async fn check_output_name(&self) -> Result<(), AppError>;
+}
+
+/// Triton clients for both models, speaking the configured `triton_protocol`
+pub struct Repositories {
+    pub embedding: Box<dyn EmbeddingRepository>,
+    /// Embedding clients for `embedding_model_routes`, by request model name
+    pub embedding_routes: HashMap<String, Box<dyn EmbeddingRepository>>,
+    pub reranking: Box<dyn RerankingRepository>,
+}
+
+/// Builds the embedding and reranking clients on one shared connection pool
+/// (HTTP) or channel (gRPC).
+pub fn build_repositories() -> Result<Repositories, AppError> {
+    let settings = Settings::get();
+
+    match settings.triton_protocol {
+        TritonProtocol::Http => {
+            let http_client = build_http_client()?;
+            let embedding_routes = settings.embedding_model_routes
+                .iter()
+                .map(|(name, model_name)| {
+                    let client: Box<dyn EmbeddingRepository> = Box::new(TritonClient::new(
+                        http_client.clone(),
+                        model_name.clone(),
+                        settings.embedding_output_name.clone(),
+                    ));
+                    (name.clone(), client)
+                })
+                .collect();
+            Ok(Repositories {
+                embedding: Box::new(TritonClient::new(
+                    http_client.clone(),
+                    settings.embedding_model_name.clone(),
+                    settings.embedding_output_name.clone(),
+                )),
+                embedding_routes,
+                reranking: Box::new(TritonClient::new(
+                    http_client,
+                    settings.reranker_model_name.clone(),
+                    settings.reranker_output_name.clone(),
+                )),
+            })
+        }
+        TritonProtocol::Grpc => {
+            let channel = build_grpc_channel()?;
+            let embedding_routes = settings.embedding_model_routes
+                .iter()
+                .map(|(name, model_name)| {
+                    let client: Box<dyn EmbeddingRepository> = Box::new(TritonGrpcClient::new(
+                        channel.clone(),
+                        model_name.clone(),
+                        settings.embedding_output_name.clone(),
+                    )?);
+                    Ok((name.clone(), client))
+                })
+                .collect::<Result<_, AppError>>()?;
+            Ok(Repositories {
+                embedding: Box::new(TritonGrpcClient::new(
+                    channel.clone(),
+                    settings.embedding_model_name.clone(),
+                    settings.embedding_output_name.clone(),
+                )?),
+                embedding_routes,
+                reranking: Box::new(TritonGrpcClient::new(
+                    channel,
+                    settings.reranker_model_name.clone(),
+                    settings.reranker_output_name.clone(),
+                )?),
+            })
+        }
+    }
 }
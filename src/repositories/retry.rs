@@ -0,0 +1,84 @@
+use crate::config::Settings;
+use crate::error::AppError;
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+use tracing::warn;
+
+/// Longest wait between two attempts, whatever the attempt count
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Outcome of a failed Triton call attempt
+pub enum Failure {
+    /// Connection problem or 5xx response; the call may succeed if repeated
+    Transient(AppError),
+    /// Error that repeating the call won't fix, such as a 4xx response
+    Permanent(AppError),
+}
+
+/// Runs an idempotent Triton call, repeating it up to `triton_max_retries`
+/// times on transient failures with exponential backoff and jitter.
+pub async fn with_retries<T, F, Fut>(operation: &str, mut call: F) -> Result<T, AppError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Failure>>,
+{
+    let settings = Settings::get();
+    let max_retries = settings.triton_max_retries;
+    let base = Duration::from_millis(settings.triton_retry_backoff_ms);
+
+    let mut attempt = 0;
+    loop {
+        match call().await {
+            Ok(value) => return Ok(value),
+            Err(Failure::Permanent(e)) => return Err(e),
+            Err(Failure::Transient(e)) if attempt >= max_retries => return Err(e),
+            Err(Failure::Transient(e)) => {
+                attempt += 1;
+                let delay = backoff(base, attempt);
+                warn!("{} failed (attempt {}/{}): {}; retrying in {}ms",
+                      operation, attempt, max_retries + 1, e, delay.as_millis());
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+/// Caps a caller's per-request timeout at the client-wide network timeout.
+pub fn effective_timeout(timeout: Option<Duration>) -> Option<Duration> {
+    let max = Duration::from_secs(Settings::get().triton_http_network_timeout);
+    timeout.map(|timeout| timeout.min(max))
+}
+
+/// Bounds a whole Triton call, retries included, by the caller's timeout.
+/// Running out of time is reported as a timeout, not a connection failure.
+pub async fn with_deadline<T>(
+    timeout: Option<Duration>,
+    operation: &str,
+    call: impl Future<Output = Result<T, AppError>>,
+) -> Result<T, AppError> {
+    let Some(timeout) = timeout else {
+        return call.await;
+    };
+
+    tokio::time::timeout(timeout, call).await.unwrap_or_else(|_| {
+        warn!("{} exceeded the request timeout of {}ms", operation, timeout.as_millis());
+        Err(AppError::TritonConnection(format!(
+            "{} timed out after the request's timeout_ms of {}; Triton is reachable but did not answer in time",
+            operation,
+            timeout.as_millis()
+        )))
+    })
+}
+
+/// `base * 2^(attempt - 1)` capped at `MAX_BACKOFF`, scaled by a random
+/// factor in `[0.5, 1.0)` so concurrent retries don't land together.
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    let exponential = base
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(MAX_BACKOFF);
+    let random = RandomState::new().build_hasher().finish();
+    let jitter = 0.5 + (random % 1000) as f64 / 2000.0;
+    exponential.mul_f64(jitter)
+}
@@ -1,22 +1,50 @@
 use crate::error::AppError;
-use crate::config::Settings;
-use reqwest::Client;
+use crate::config::{Settings, TritonHttpVersion};
+use crate::metrics::Metrics;
+use crate::models::OutputTensorInfo;
+use crate::repositories::retry::{effective_timeout, with_deadline, with_retries, Failure};
+use crate::repositories::{EmbeddingRepository, RerankingRepository};
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Client, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::time::Duration;
-use tracing::{info, error};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::net::TcpStream;
+use tracing::{info, warn, error};
+
+/// Header giving the JSON header length of a binary-data inference body
+const INFERENCE_HEADER_CONTENT_LENGTH: &str = "Inference-Header-Content-Length";
 
 #[derive(Debug, Serialize)]
 struct TritonInferenceInput {
     name: String,
     shape: Vec<usize>,
     datatype: String,
-    data: Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<i64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parameters: Option<TensorParameters>,
 }
 
 #[derive(Debug, Serialize)]
 struct TritonInferenceOutput {
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parameters: Option<TensorParameters>,
+}
+
+/// Binary data extension parameters of an input or output tensor
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct TensorParameters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    binary_data: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    binary_data_size: Option<usize>,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,126 +63,449 @@ struct TritonOutputData {
     name: String,
     shape: Vec<usize>,
     datatype: String,
+    #[serde(default)]
     data: Vec<f32>,
+    #[serde(default)]
+    parameters: Option<TensorParameters>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TritonModelMetadata {
+    #[serde(default)]
+    versions: Vec<String>,
+    #[serde(default)]
+    outputs: Vec<TritonTensorMetadata>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TritonTensorMetadata {
+    name: String,
+}
+
+/// Builds the configured extra headers sent with every Triton request,
+/// rejecting invalid header names or values.
+fn extra_headers(headers: &HashMap<String, String>) -> Result<HeaderMap, AppError> {
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+            AppError::Internal(format!("Invalid header name in TRITON_EXTRA_HEADERS: '{}'", name))
+        })?;
+        let header_value = HeaderValue::from_str(value).map_err(|_| {
+            AppError::Internal(format!("Invalid value for header '{}' in TRITON_EXTRA_HEADERS", name))
+        })?;
+        header_map.insert(header_name, header_value);
+    }
+    Ok(header_map)
+}
+
+/// Parses an inference response body. When Triton reports a JSON header
+/// length, outputs sent as binary data are read in order from the bytes
+/// following the header.
+fn decode_infer_response(body: &[u8], header_len: Option<usize>) -> Result<TritonInferResponse, AppError> {
+    let parse_error = |e: serde_json::Error| AppError::Inference(format!("Failed to parse response: {}", e));
+
+    let Some(header_len) = header_len else {
+        return serde_json::from_slice(body).map_err(parse_error);
+    };
+    if header_len > body.len() {
+        return Err(AppError::Inference(format!(
+            "Inference header length {} exceeds response size {}",
+            header_len,
+            body.len()
+        )));
+    }
+
+    let (header, mut binary) = body.split_at(header_len);
+    let mut response: TritonInferResponse = serde_json::from_slice(header).map_err(parse_error)?;
+
+    for output in &mut response.outputs {
+        let Some(size) = output.parameters.as_ref().and_then(|p| p.binary_data_size) else {
+            continue;
+        };
+        if output.datatype != "FP32" || size % 4 != 0 || size > binary.len() {
+            return Err(AppError::Inference(format!(
+                "Invalid binary output {}: {} bytes of {} with {} bytes remaining",
+                output.name,
+                size,
+                output.datatype,
+                binary.len()
+            )));
+        }
+
+        let (data, rest) = binary.split_at(size);
+        output.data = data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        binary = rest;
+    }
+
+    Ok(response)
+}
+
+/// Embedding dimension of an output tensor. Uses the reported shape when
+/// present, then the configured fallback, then `data.len() / batch_size`,
+/// and errors only if none of these evenly covers the returned data.
+pub(crate) fn resolve_embedding_dim(shape: &[usize], data_len: usize, batch_size: usize) -> Result<usize, AppError> {
+    if let Some(&dim) = shape.get(1).filter(|&&dim| dim > 0) {
+        return Ok(dim);
+    }
+
+    if let Some(dim) = Settings::get().fallback_embedding_dim {
+        if dim > 0 && data_len == dim * batch_size {
+            warn!("Triton output shape {:?} has no embedding dimension, using configured {}", shape, dim);
+            return Ok(dim);
+        }
+    }
+
+    if data_len > 0 && data_len.checked_rem(batch_size) == Some(0) {
+        let dim = data_len / batch_size;
+        warn!("Triton output shape {:?} has no embedding dimension, inferred {} from {} values for batch of {}",
+              shape, dim, data_len, batch_size);
+        return Ok(dim);
+    }
+
+    Err(AppError::Inference(format!(
+        "Cannot determine embedding dimension: output shape {:?} with {} values for batch of {}",
+        shape, data_len, batch_size
+    )))
+}
+
+/// Why a Triton address failed the startup reachability probe
+#[derive(Error, Debug)]
+pub enum TritonProbeError {
+    #[error("DNS lookup for '{address}' failed: {reason}")]
+    Dns { address: String, reason: String },
+
+    #[error("connection refused by {resolved} (resolved from '{address}'); is Triton listening on that port?")]
+    Refused { address: String, resolved: SocketAddr },
+
+    #[error("timed out after {timeout:?} connecting to {resolved} (resolved from '{address}')")]
+    Timeout { address: String, resolved: SocketAddr, timeout: Duration },
+
+    #[error("failed to connect to {resolved} (resolved from '{address}'): {reason}")]
+    Connect { address: String, resolved: SocketAddr, reason: String },
+}
+
+/// Resolves a `host:port` Triton address and opens a TCP connection to it,
+/// reporting which of the two steps failed.
+pub async fn probe_reachability(address: &str, timeout: Duration) -> Result<SocketAddr, TritonProbeError> {
+    let resolved = tokio::net::lookup_host(address)
+        .await
+        .map_err(|e| TritonProbeError::Dns { address: address.to_string(), reason: e.to_string() })?
+        .next()
+        .ok_or_else(|| TritonProbeError::Dns {
+            address: address.to_string(),
+            reason: "no addresses returned".to_string(),
+        })?;
+
+    match tokio::time::timeout(timeout, TcpStream::connect(resolved)).await {
+        Ok(Ok(_)) => Ok(resolved),
+        Ok(Err(e)) if e.kind() == ErrorKind::ConnectionRefused => Err(TritonProbeError::Refused {
+            address: address.to_string(),
+            resolved,
+        }),
+        Ok(Err(e)) => Err(TritonProbeError::Connect {
+            address: address.to_string(),
+            resolved,
+            reason: e.to_string(),
+        }),
+        Err(_) => Err(TritonProbeError::Timeout {
+            address: address.to_string(),
+            resolved,
+            timeout,
+        }),
+    }
 }
 
 pub struct TritonClient {
     client: Client,
     triton_url: String,
+    fallback_url: Option<String>,
     model_name: String,
+    output_name: String,
+    /// Whether the HTTP version of a Triton response has been logged yet
+    version_logged: AtomicBool,
+}
+
+/// Builds the HTTP client shared by every `TritonClient`, so all models
+/// reuse one connection pool to the Triton host.
+pub fn build_http_client() -> Result<Client, AppError> {
+    let settings = Settings::get();
+    let timeout = Duration::from_secs(settings.triton_http_network_timeout);
+    
+    let connect_timeout = Duration::from_secs(settings.triton_http_connection_timeout);
+    
+    let builder = Client::builder()
+        .timeout(timeout)
+        .connect_timeout(connect_timeout)
+        .default_headers(extra_headers(&settings.triton_extra_headers)?);
+
+    let builder = match settings.triton_http_version {
+        TritonHttpVersion::Auto => builder,
+        TritonHttpVersion::Http1 => builder.http1_only(),
+        TritonHttpVersion::Http2 => builder.http2_prior_knowledge(),
+    };
+
+    builder
+        .build()
+        .map_err(|e| AppError::TritonConnection(e.to_string()))
 }
 
 impl TritonClient {
-    pub fn new(model_name: String) -> Result<Self, AppError> {
+    /// Creates a client for one model on top of a shared HTTP client; the
+    /// model name only affects request URLs and `output_name` is the output
+    /// tensor requested from inference.
+    pub fn new(client: Client, model_name: String, output_name: String) -> Self {
         let settings = Settings::get();
-        let timeout = Duration::from_secs(settings.triton_http_network_timeout);
-        
-        let client = Client::builder()
-            .timeout(timeout)
-            .build()
-            .map_err(|e| AppError::TritonConnection(e.to_string()))?;
-
-        Ok(Self {
+
+        Self {
             client,
             triton_url: format!("http://{}", settings.triton_url),
+            fallback_url: settings.triton_fallback_url
+                .as_ref()
+                .filter(|url| !url.is_empty())
+                .map(|url| format!("http://{}", url)),
             model_name,
-        })
+            output_name,
+            version_logged: AtomicBool::new(false),
+        }
+    }
+
+    /// Sends a request built against the primary Triton URL, retrying it once
+    /// against the fallback URL when the primary can't be reached or times out.
+    async fn send<F>(&self, build: F) -> Result<Response, AppError>
+    where
+        F: Fn(&str) -> RequestBuilder,
+    {
+        match build(&self.triton_url).send().await {
+            Ok(response) => {
+                info!("Request served by Triton at {}", self.triton_url);
+                Ok(response)
+            }
+            Err(e) if e.is_connect() || e.is_timeout() => {
+                let Some(fallback_url) = &self.fallback_url else {
+                    return Err(e.into());
+                };
+
+                warn!("Primary Triton at {} unavailable ({}), retrying against fallback {}",
+                      self.triton_url, e, fallback_url);
+                let response = build(fallback_url).send().await?;
+                info!("Request served by Triton at {}", fallback_url);
+                Ok(response)
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
     pub async fn is_server_live(&self) -> Result<bool, AppError> {
-        let url = format!("{}/v2/health/live", self.triton_url);
-        let response = self.client.get(&url).send().await?;
+        let response = self
+            .send(|base| self.client.get(format!("{}/v2/health/live", base)))
+            .await?;
+        if !self.version_logged.swap(true, Ordering::Relaxed) {
+            info!("Triton connection for model {} uses {:?}", self.model_name, response.version());
+        }
         Ok(response.status().is_success())
     }
 
     pub async fn is_model_ready(&self) -> Result<bool, AppError> {
-        let url = format!("{}/v2/models/{}/ready", self.triton_url, self.model_name);
-        let response = self.client.get(&url).send().await?;
+        let response = self
+            .send(|base| self.client.get(format!("{}/v2/models/{}/ready", base, self.model_name)))
+            .await?;
         Ok(response.status().is_success())
     }
 
-    pub async fn get_embeddings(
-        &self,
-        input_ids: &[Vec<i64>],
-        attention_mask: &[Vec<i64>],
-        task_id: i64,
-    ) -> Result<Vec<Vec<f32>>, AppError> {
-        let batch_size = input_ids.len();
-        let seq_length = input_ids[0].len();
+    async fn get_model_metadata(&self) -> Result<TritonModelMetadata, AppError> {
+        let response = self
+            .send(|base| self.client.get(format!("{}/v2/models/{}", base, self.model_name)))
+            .await?;
 
-        info!("Preparing inference request: batch_size={}, seq_length={}, task_id={}", 
-              batch_size, seq_length, task_id);
+        if !response.status().is_success() {
+            return Err(AppError::TritonConnection(format!(
+                "Failed to fetch metadata for model {}: {}",
+                self.model_name,
+                response.status()
+            )));
+        }
 
-        // Flatten input_ids and attention_mask
-        let flat_input_ids: Vec<i64> = input_ids.iter().flatten().copied().collect();
-        let flat_attention_mask: Vec<i64> = attention_mask.iter().flatten().copied().collect();
-        let task_ids = vec![task_id; batch_size];
+        Ok(response.json().await?)
+    }
 
-        let request = TritonInferRequest {
-            inputs: vec![
-                TritonInferenceInput {
-                    name: "input_ids".to_string(),
-                    shape: vec![batch_size, seq_length],
-                    datatype: "INT64".to_string(),
-                    data: flat_input_ids.iter().map(|&x| json!(x)).collect(),
-                },
-                TritonInferenceInput {
-                    name: "attention_mask".to_string(),
-                    shape: vec![batch_size, seq_length],
-                    datatype: "INT64".to_string(),
-                    data: flat_attention_mask.iter().map(|&x| json!(x)).collect(),
-                },
+    /// Latest version of the model available on Triton, from its metadata.
+    pub async fn get_model_version(&self) -> Result<Option<String>, AppError> {
+        let metadata = self.get_model_metadata().await?;
+        Ok(metadata.versions
+            .into_iter()
+            .max_by_key(|version| version.parse::<u64>().unwrap_or(0)))
+    }
+
+    /// Verifies that the model declares the configured output tensor,
+    /// failing with the available output names when it doesn't.
+    pub async fn check_output_name(&self) -> Result<(), AppError> {
+        let metadata = self.get_model_metadata().await?;
+        if metadata.outputs.iter().any(|output| output.name == self.output_name) {
+            return Ok(());
+        }
+
+        let available: Vec<&str> = metadata.outputs.iter().map(|output| output.name.as_str()).collect();
+        Err(AppError::Internal(format!(
+            "Model {} has no output tensor named '{}'; available outputs: [{}]",
+            self.model_name,
+            self.output_name,
+            available.join(", ")
+        )))
+    }
+
+    /// Runs inference with INT64 `[rows, row_len]` inputs and returns the
+    /// requested output. With `triton_binary_data` set, tensors travel as raw
+    /// little-endian bytes after the JSON header (Triton's binary data extension).
+    /// `timeout` bounds each HTTP attempt and the call as a whole.
+    async fn infer(
+        &self,
+        inputs: &[(&str, &[Vec<i64>])],
+        timeout: Option<Duration>,
+    ) -> Result<TritonOutputData, AppError> {
+        let binary = Settings::get().triton_binary_data;
+        let timeout = effective_timeout(timeout);
+        let mut raw = Vec::new();
+
+        let inputs = inputs
+            .iter()
+            .map(|&(name, rows)| {
+                let shape = vec![rows.len(), rows.first().map_or(0, Vec::len)];
+                let values = rows.iter().flatten().copied();
+                let (data, parameters) = if binary {
+                    let start = raw.len();
+                    raw.extend(values.flat_map(i64::to_le_bytes));
+                    let size = raw.len() - start;
+                    (None, Some(TensorParameters { binary_data_size: Some(size), ..Default::default() }))
+                } else {
+                    (Some(values.collect()), None)
+                };
                 TritonInferenceInput {
-                    name: "task_id".to_string(),
-                    shape: vec![batch_size, 1],
+                    name: name.to_string(),
+                    shape,
                     datatype: "INT64".to_string(),
-                    data: task_ids.iter().map(|&x| json!(x)).collect(),
-                },
-            ],
+                    data,
+                    parameters,
+                }
+            })
+            .collect();
+
+        let request = TritonInferRequest {
+            inputs,
             outputs: vec![TritonInferenceOutput {
-                name: "13049".to_string(),
+                name: self.output_name.clone(),
+                parameters: binary.then(|| TensorParameters { binary_data: Some(true), ..Default::default() }),
             }],
         };
 
-        let url = format!("{}/v2/models/{}/infer", self.triton_url, self.model_name);
-        info!("Sending inference request to: {}", url);
-        
-        let response = self.client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await?;
-
-        let status = response.status();
-        info!("Received response with status: {}", status);
+        let mut body = serde_json::to_vec(&request)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize inference request: {}", e)))?;
+        let header_len = body.len();
+        body.extend_from_slice(&raw);
+
+        let inference = with_retries("Triton inference", || async {
+            let response = self
+                .send(|base| {
+                    let mut builder = self.client
+                        .post(format!("{}/v2/models/{}/infer", base, self.model_name))
+                        .body(body.clone());
+                    if let Some(timeout) = timeout {
+                        builder = builder.timeout(timeout);
+                    }
+                    if binary {
+                        builder
+                            .header(CONTENT_TYPE, "application/octet-stream")
+                            .header(INFERENCE_HEADER_CONTENT_LENGTH, header_len)
+                    } else {
+                        builder.header(CONTENT_TYPE, "application/json")
+                    }
+                })
+                .await
+                .map_err(Failure::Transient)?;
+
+            let status = response.status();
+            info!("Received response with status: {}", status);
+
+            if status.is_success() {
+                return Ok(response);
+            }
 
-        if !status.is_success() {
             let error_text = response.text().await.unwrap_or_default();
             error!("Triton inference failed with status {}: {}", status, error_text);
-            return Err(AppError::Inference(format!("Triton returned error {}: {}", status, error_text)));
-        }
+            let error = AppError::Inference(format!("Triton returned error {}: {}", status, error_text));
+            Err(if status.is_server_error() { Failure::Transient(error) } else { Failure::Permanent(error) })
+        });
+        let response = with_deadline(timeout, "Triton inference", inference).await?;
+
+        let response_header_len = response
+            .headers()
+            .get(INFERENCE_HEADER_CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        let bytes = response.bytes().await
+            .map_err(|e| AppError::Inference(format!("Failed to read response: {}", e)))?;
+
+        decode_infer_response(&bytes, response_header_len)?
+            .outputs
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::Inference("No output from Triton".to_string()))
+    }
+
+    pub async fn get_embeddings(
+        &self,
+        input_ids: &[Vec<i64>],
+        attention_mask: &[Vec<i64>],
+        task_id: i64,
+        timeout: Option<Duration>,
+    ) -> Result<(Vec<f32>, usize, OutputTensorInfo), AppError> {
+        let batch_size = input_ids.len();
+        let seq_length = input_ids[0].len();
 
-        let infer_response: TritonInferResponse = response.json().await
-            .map_err(|e| AppError::Inference(format!("Failed to parse response: {}", e)))?;
+        info!("Preparing inference request: batch_size={}, seq_length={}, task_id={}", 
+              batch_size, seq_length, task_id);
 
-        if let Some(output) = infer_response.outputs.first() {
-            let embedding_dim = output.shape.get(1).copied().unwrap_or(0);
-            let embeddings: Vec<Vec<f32>> = output.data
-                .chunks(embedding_dim)
-                .map(|chunk| chunk.to_vec())
-                .collect();
+        let task_ids = vec![vec![task_id]; batch_size];
 
-            info!("Embeddings shape: [{}, {}]", embeddings.len(), embedding_dim);
-            Ok(embeddings)
-        } else {
-            Err(AppError::Inference("No output from Triton".to_string()))
+        info!("Sending inference request for model: {}", self.model_name);
+        let started = Instant::now();
+        let output = self
+            .infer(&[
+                ("input_ids", input_ids),
+                ("attention_mask", attention_mask),
+                ("task_id", &task_ids),
+            ], timeout)
+            .await?;
+        let inference_ms = started.elapsed().as_millis() as u64;
+        Metrics::get()
+            .embedding_triton_latency
+            .observe(inference_ms);
+
+        let embedding_dim = resolve_embedding_dim(&output.shape, output.data.len(), batch_size)?;
+        if output.data.len() != batch_size * embedding_dim {
+            return Err(AppError::Inference(format!(
+                "Triton returned {} values for a batch of {} with embedding dimension {}",
+                output.data.len(), batch_size, embedding_dim
+            )));
         }
+
+        info!(inference_ms, "Embeddings shape: [{}, {}]", batch_size, embedding_dim);
+        let tensor_info = OutputTensorInfo {
+            name: output.name,
+            datatype: output.datatype,
+            shape: output.shape,
+        };
+        Ok((output.data, embedding_dim, tensor_info))
     }
 
     pub async fn get_scores(
         &self,
         input_ids: &[Vec<i64>],
         attention_mask: &[Vec<i64>],
+        token_type_ids: Option<&[Vec<i64>]>,
+        timeout: Option<Duration>,
     ) -> Result<Vec<f32>, AppError> {
         if input_ids.is_empty() {
             return Err(AppError::Validation("input_ids cannot be empty".to_string()));
@@ -187,55 +538,69 @@ impl TritonClient {
         info!("Preparing reranking inference request: batch_size={}, seq_length={}", 
               batch_size, seq_length);
 
-        let flat_input_ids: Vec<i64> = input_ids.iter().flatten().copied().collect();
-        let flat_attention_mask: Vec<i64> = attention_mask.iter().flatten().copied().collect();
+        info!("Sending reranking inference request for model: {}", self.model_name);
+        let started = Instant::now();
+        let mut inputs = vec![("input_ids", input_ids), ("attention_mask", attention_mask)];
+        if let Some(token_type_ids) = token_type_ids {
+            inputs.push(("token_type_ids", token_type_ids));
+        }
+        let output = self.infer(&inputs, timeout).await?;
+        let inference_ms = started.elapsed().as_millis() as u64;
+        Metrics::get()
+            .reranker_triton_latency
+            .observe(inference_ms);
+
+        info!(inference_ms, "Reranking scores shape: [{}]", output.data.len());
+        Ok(output.data)
+    }
+}
+
+#[async_trait]
+impl EmbeddingRepository for TritonClient {
+    async fn generate_embeddings(
+        &self,
+        input_ids: &[Vec<i64>],
+        attention_mask: &[Vec<i64>],
+        task_id: i64,
+        timeout: Option<Duration>,
+    ) -> Result<(Vec<f32>, usize, OutputTensorInfo), AppError> {
+        self.get_embeddings(input_ids, attention_mask, task_id, timeout).await
+    }
 
-        let request = TritonInferRequest {
-            inputs: vec![
-                TritonInferenceInput {
-                    name: "input_ids".to_string(),
-                    shape: vec![batch_size, seq_length],
-                    datatype: "INT64".to_string(),
-                    data: flat_input_ids.iter().map(|&x| json!(x)).collect(),
-                },
-                TritonInferenceInput {
-                    name: "attention_mask".to_string(),
-                    shape: vec![batch_size, seq_length],
-                    datatype: "INT64".to_string(),
-                    data: flat_attention_mask.iter().map(|&x| json!(x)).collect(),
-                },
-            ],
-            outputs: vec![TritonInferenceOutput {
-                name: "logits".to_string(),
-            }],
-        };
+    async fn is_ready(&self) -> Result<bool, AppError> {
+        Ok(self.is_server_live().await? && self.is_model_ready().await?)
+    }
 
-        let url = format!("{}/v2/models/{}/infer", self.triton_url, self.model_name);
-        info!("Sending reranking inference request to: {}", url);
-        
-        let response = self.client
-            .post(&url)
-            .json(&request)
-            .send()
-            .await?;
+    async fn get_model_version(&self) -> Result<Option<String>, AppError> {
+        TritonClient::get_model_version(self).await
+    }
 
-        let status = response.status();
-        info!("Received reranking response with status: {}", status);
+    async fn check_output_name(&self) -> Result<(), AppError> {
+        TritonClient::check_output_name(self).await
+    }
+}
 
-        if !status.is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            error!("Triton reranking inference failed with status {}: {}", status, error_text);
-            return Err(AppError::Inference(format!("Triton returned error {}: {}", status, error_text)));
-        }
+#[async_trait]
+impl RerankingRepository for TritonClient {
+    async fn generate_scores(
+        &self,
+        input_ids: &[Vec<i64>],
+        attention_mask: &[Vec<i64>],
+        token_type_ids: Option<&[Vec<i64>]>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<f32>, AppError> {
+        self.get_scores(input_ids, attention_mask, token_type_ids, timeout).await
+    }
 
-        let infer_response: TritonInferResponse = response.json().await
-            .map_err(|e| AppError::Inference(format!("Failed to parse response: {}", e)))?;
+    async fn is_ready(&self) -> Result<bool, AppError> {
+        Ok(self.is_server_live().await? && self.is_model_ready().await?)
+    }
 
-        if let Some(output) = infer_response.outputs.first() {
-            info!("Reranking scores shape: [{}]", output.data.len());
-            Ok(output.data.clone())
-        } else {
-            Err(AppError::Inference("No output from Triton".to_string()))
-        }
+    async fn get_model_version(&self) -> Result<Option<String>, AppError> {
+        TritonClient::get_model_version(self).await
+    }
+
+    async fn check_output_name(&self) -> Result<(), AppError> {
+        TritonClient::check_output_name(self).await
     }
 }
@@ -0,0 +1,445 @@
+use crate::config::Settings;
+use crate::error::AppError;
+use crate::metrics::Metrics;
+use crate::models::OutputTensorInfo;
+use crate::repositories::retry::{effective_timeout, with_deadline, with_retries, Failure};
+use crate::repositories::triton_client::resolve_embedding_dim;
+use crate::repositories::{EmbeddingRepository, RerankingRepository};
+use async_trait::async_trait;
+use std::time::{Duration, Instant};
+use tonic::codec::ProstCodec;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::{AsciiMetadataKey, AsciiMetadataValue, MetadataMap};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Status};
+use tracing::{error, info};
+
+// Subset of Triton's `grpc_service.proto` (package `inference`) used by this
+// client; fields not listed here are skipped when decoding.
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ServerLiveRequest {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ServerLiveResponse {
+    #[prost(bool, tag = "1")]
+    live: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ModelReadyRequest {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    version: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ModelReadyResponse {
+    #[prost(bool, tag = "1")]
+    ready: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ModelMetadataRequest {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    version: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ModelMetadataResponse {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, repeated, tag = "2")]
+    versions: Vec<String>,
+    #[prost(message, repeated, tag = "5")]
+    outputs: Vec<TensorMetadata>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TensorMetadata {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ModelInferRequest {
+    #[prost(string, tag = "1")]
+    model_name: String,
+    #[prost(message, repeated, tag = "5")]
+    inputs: Vec<InferInputTensor>,
+    #[prost(message, repeated, tag = "6")]
+    outputs: Vec<InferRequestedOutputTensor>,
+    /// Little-endian tensor bytes, one entry per input in order
+    #[prost(bytes = "vec", repeated, tag = "7")]
+    raw_input_contents: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct InferInputTensor {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    datatype: String,
+    #[prost(int64, repeated, tag = "3")]
+    shape: Vec<i64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct InferRequestedOutputTensor {
+    #[prost(string, tag = "1")]
+    name: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct ModelInferResponse {
+    #[prost(message, repeated, tag = "5")]
+    outputs: Vec<InferOutputTensor>,
+    /// Little-endian tensor bytes, one entry per output in order
+    #[prost(bytes = "vec", repeated, tag = "6")]
+    raw_output_contents: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct InferOutputTensor {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    datatype: String,
+    #[prost(int64, repeated, tag = "3")]
+    shape: Vec<i64>,
+    #[prost(message, optional, tag = "5")]
+    contents: Option<InferTensorContents>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct InferTensorContents {
+    #[prost(float, repeated, tag = "6")]
+    fp32_contents: Vec<f32>,
+}
+
+const SERVER_LIVE_PATH: &str = "/inference.GRPCInferenceService/ServerLive";
+const MODEL_READY_PATH: &str = "/inference.GRPCInferenceService/ModelReady";
+const MODEL_METADATA_PATH: &str = "/inference.GRPCInferenceService/ModelMetadata";
+const MODEL_INFER_PATH: &str = "/inference.GRPCInferenceService/ModelInfer";
+
+/// Builds the gRPC channel shared by every `TritonGrpcClient`. The channel
+/// connects lazily, so an unreachable Triton fails requests, not startup.
+pub fn build_grpc_channel() -> Result<Channel, AppError> {
+    let settings = Settings::get();
+
+    let endpoint = Endpoint::from_shared(format!("http://{}", settings.triton_grpc_url))
+        .map_err(|e| AppError::Internal(format!("Invalid TRITON_GRPC_URL '{}': {}", settings.triton_grpc_url, e)))?
+        .connect_timeout(Duration::from_secs(settings.triton_http_connection_timeout))
+        .timeout(Duration::from_secs(settings.triton_http_network_timeout));
+
+    Ok(endpoint.connect_lazy())
+}
+
+/// Sends the configured extra Triton headers as gRPC metadata.
+fn extra_metadata() -> Result<MetadataMap, AppError> {
+    let mut metadata = MetadataMap::new();
+    for (name, value) in &Settings::get().triton_extra_headers {
+        let key = AsciiMetadataKey::from_bytes(name.as_bytes()).map_err(|_| {
+            AppError::Internal(format!("Invalid header name in TRITON_EXTRA_HEADERS: '{}'", name))
+        })?;
+        let value: AsciiMetadataValue = value.parse().map_err(|_| {
+            AppError::Internal(format!("Invalid value for header '{}' in TRITON_EXTRA_HEADERS", name))
+        })?;
+        metadata.insert(key, value);
+    }
+    Ok(metadata)
+}
+
+/// Binary INT64 input tensor of shape `[rows, row_len]`.
+fn int64_input(name: &str, rows: &[Vec<i64>]) -> (InferInputTensor, Vec<u8>) {
+    let row_len = rows.first().map_or(0, Vec::len);
+    let tensor = InferInputTensor {
+        name: name.to_string(),
+        datatype: "INT64".to_string(),
+        shape: vec![rows.len() as i64, row_len as i64],
+    };
+    let bytes = rows.iter().flatten().flat_map(|value| value.to_le_bytes()).collect();
+    (tensor, bytes)
+}
+
+/// FP32 values of the first output, from the raw bytes Triton returns for
+/// binary requests or the typed contents otherwise.
+fn first_output(response: &ModelInferResponse) -> Result<(&InferOutputTensor, Vec<f32>), AppError> {
+    let output = response.outputs
+        .first()
+        .ok_or_else(|| AppError::Inference("No output from Triton".to_string()))?;
+
+    if output.datatype != "FP32" {
+        return Err(AppError::Inference(format!(
+            "Unsupported output datatype {} for tensor {}; expected FP32",
+            output.datatype, output.name
+        )));
+    }
+
+    let values = match response.raw_output_contents.first() {
+        Some(raw) if raw.len() % 4 != 0 => {
+            return Err(AppError::Inference(format!(
+                "Raw output for tensor {} has {} bytes, not a multiple of 4",
+                output.name,
+                raw.len()
+            )));
+        }
+        Some(raw) => raw
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect(),
+        None => output.contents
+            .as_ref()
+            .map(|contents| contents.fp32_contents.clone())
+            .unwrap_or_default(),
+    };
+    Ok((output, values))
+}
+
+fn connection_error(status: Status) -> AppError {
+    AppError::TritonConnection(format!("Triton gRPC call failed ({:?}): {}", status.code(), status.message()))
+}
+
+/// Inference failures, keeping transport problems distinguishable as 503s.
+fn inference_error(status: Status) -> AppError {
+    match status.code() {
+        Code::Unavailable | Code::DeadlineExceeded | Code::Cancelled => connection_error(status),
+        code => {
+            error!("Triton gRPC inference failed ({:?}): {}", code, status.message());
+            AppError::Inference(format!("Triton returned error {:?}: {}", code, status.message()))
+        }
+    }
+}
+
+/// Triton client over the KServe v2 gRPC protocol. Token tensors are sent as
+/// raw little-endian bytes instead of one JSON value per element.
+pub struct TritonGrpcClient {
+    channel: Channel,
+    metadata: MetadataMap,
+    model_name: String,
+    output_name: String,
+}
+
+impl TritonGrpcClient {
+    pub fn new(channel: Channel, model_name: String, output_name: String) -> Result<Self, AppError> {
+        Ok(Self {
+            channel,
+            metadata: extra_metadata()?,
+            model_name,
+            output_name,
+        })
+    }
+
+    /// Sends one unary call, with a `grpc-timeout` when `timeout` is given.
+    async fn unary<Req, Resp>(&self, path: &'static str, message: Req, timeout: Option<Duration>) -> Result<Resp, Status>
+    where
+        Req: prost::Message + Send + Sync + 'static,
+        Resp: prost::Message + Default + Send + Sync + 'static,
+    {
+        let mut grpc = tonic::client::Grpc::new(self.channel.clone())
+            .max_decoding_message_size(usize::MAX)
+            .max_encoding_message_size(usize::MAX);
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unavailable(format!("channel not ready: {}", e)))?;
+
+        let mut request = Request::new(message);
+        *request.metadata_mut() = self.metadata.clone();
+        if let Some(timeout) = timeout {
+            request.set_timeout(timeout);
+        }
+
+        let response = grpc
+            .unary(request, PathAndQuery::from_static(path), ProstCodec::default())
+            .await?;
+        Ok(response.into_inner())
+    }
+
+    async fn get_model_metadata(&self) -> Result<ModelMetadataResponse, AppError> {
+        self.unary(
+            MODEL_METADATA_PATH,
+            ModelMetadataRequest { name: self.model_name.clone(), version: String::new() },
+            None,
+        )
+        .await
+        .map_err(connection_error)
+    }
+
+    /// Runs inference; `timeout` bounds each attempt and the call as a whole.
+    async fn infer(
+        &self,
+        inputs: Vec<(InferInputTensor, Vec<u8>)>,
+        timeout: Option<Duration>,
+    ) -> Result<ModelInferResponse, AppError> {
+        let timeout = effective_timeout(timeout);
+        let (inputs, raw_input_contents) = inputs.into_iter().unzip();
+        let request = ModelInferRequest {
+            model_name: self.model_name.clone(),
+            inputs,
+            outputs: vec![InferRequestedOutputTensor { name: self.output_name.clone() }],
+            raw_input_contents,
+        };
+
+        info!("Sending gRPC inference request for model: {}", self.model_name);
+        let inference = with_retries("Triton gRPC inference", || async {
+            self.unary(MODEL_INFER_PATH, request.clone(), timeout).await.map_err(|status| {
+                let retryable = matches!(
+                    status.code(),
+                    Code::Unavailable | Code::DeadlineExceeded | Code::Cancelled | Code::Internal
+                );
+                let error = inference_error(status);
+                if retryable { Failure::Transient(error) } else { Failure::Permanent(error) }
+            })
+        });
+        with_deadline(timeout, "Triton gRPC inference", inference).await
+    }
+
+    async fn is_ready(&self) -> Result<bool, AppError> {
+        let live: ServerLiveResponse = self
+            .unary(SERVER_LIVE_PATH, ServerLiveRequest {}, None)
+            .await
+            .map_err(connection_error)?;
+        let ready: ModelReadyResponse = self
+            .unary(
+                MODEL_READY_PATH,
+                ModelReadyRequest { name: self.model_name.clone(), version: String::new() },
+                None,
+            )
+            .await
+            .map_err(connection_error)?;
+        Ok(live.live && ready.ready)
+    }
+
+    async fn get_model_version(&self) -> Result<Option<String>, AppError> {
+        let metadata = self.get_model_metadata().await?;
+        Ok(metadata.versions
+            .into_iter()
+            .max_by_key(|version| version.parse::<u64>().unwrap_or(0)))
+    }
+
+    async fn check_output_name(&self) -> Result<(), AppError> {
+        let metadata = self.get_model_metadata().await?;
+        if metadata.outputs.iter().any(|output| output.name == self.output_name) {
+            return Ok(());
+        }
+
+        let available: Vec<&str> = metadata.outputs.iter().map(|output| output.name.as_str()).collect();
+        Err(AppError::Internal(format!(
+            "Model {} has no output tensor named '{}'; available outputs: [{}]",
+            self.model_name,
+            self.output_name,
+            available.join(", ")
+        )))
+    }
+}
+
+#[async_trait]
+impl EmbeddingRepository for TritonGrpcClient {
+    async fn generate_embeddings(
+        &self,
+        input_ids: &[Vec<i64>],
+        attention_mask: &[Vec<i64>],
+        task_id: i64,
+        timeout: Option<Duration>,
+    ) -> Result<(Vec<f32>, usize, OutputTensorInfo), AppError> {
+        let batch_size = input_ids.len();
+        info!("Preparing gRPC inference request: batch_size={}, seq_length={}, task_id={}",
+              batch_size, input_ids.first().map_or(0, Vec::len), task_id);
+
+        let task_ids = vec![vec![task_id]; batch_size];
+        let started = Instant::now();
+        let response = self
+            .infer(vec![
+                int64_input("input_ids", input_ids),
+                int64_input("attention_mask", attention_mask),
+                int64_input("task_id", &task_ids),
+            ], timeout)
+            .await?;
+        let inference_ms = started.elapsed().as_millis() as u64;
+        Metrics::get()
+            .embedding_triton_latency
+            .observe(inference_ms);
+
+        let (output, data) = first_output(&response)?;
+        let shape: Vec<usize> = output.shape.iter().map(|&dim| dim.max(0) as usize).collect();
+        let embedding_dim = resolve_embedding_dim(&shape, data.len(), batch_size)?;
+        if data.len() != batch_size * embedding_dim {
+            return Err(AppError::Inference(format!(
+                "Triton returned {} values for a batch of {} with embedding dimension {}",
+                data.len(), batch_size, embedding_dim
+            )));
+        }
+
+        info!(inference_ms, "Embeddings shape: [{}, {}]", batch_size, embedding_dim);
+        let tensor_info = OutputTensorInfo {
+            name: output.name.clone(),
+            datatype: output.datatype.clone(),
+            shape,
+        };
+        Ok((data, embedding_dim, tensor_info))
+    }
+
+    async fn is_ready(&self) -> Result<bool, AppError> {
+        TritonGrpcClient::is_ready(self).await
+    }
+
+    async fn get_model_version(&self) -> Result<Option<String>, AppError> {
+        TritonGrpcClient::get_model_version(self).await
+    }
+
+    async fn check_output_name(&self) -> Result<(), AppError> {
+        TritonGrpcClient::check_output_name(self).await
+    }
+}
+
+#[async_trait]
+impl RerankingRepository for TritonGrpcClient {
+    async fn generate_scores(
+        &self,
+        input_ids: &[Vec<i64>],
+        attention_mask: &[Vec<i64>],
+        token_type_ids: Option<&[Vec<i64>]>,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<f32>, AppError> {
+        if input_ids.is_empty() {
+            return Err(AppError::Validation("input_ids cannot be empty".to_string()));
+        }
+
+        info!("Preparing gRPC reranking inference request: batch_size={}, seq_length={}",
+              input_ids.len(), input_ids[0].len());
+
+        let mut inputs = vec![
+            int64_input("input_ids", input_ids),
+            int64_input("attention_mask", attention_mask),
+        ];
+        if let Some(token_type_ids) = token_type_ids {
+            inputs.push(int64_input("token_type_ids", token_type_ids));
+        }
+
+        let started = Instant::now();
+        let response = self.infer(inputs, timeout).await?;
+        let inference_ms = started.elapsed().as_millis() as u64;
+        Metrics::get()
+            .reranker_triton_latency
+            .observe(inference_ms);
+
+        let (_, scores) = first_output(&response)?;
+        info!(inference_ms, "Reranking scores shape: [{}]", scores.len());
+        Ok(scores)
+    }
+
+    async fn is_ready(&self) -> Result<bool, AppError> {
+        TritonGrpcClient::is_ready(self).await
+    }
+
+    async fn get_model_version(&self) -> Result<Option<String>, AppError> {
+        TritonGrpcClient::get_model_version(self).await
+    }
+
+    async fn check_output_name(&self) -> Result<(), AppError> {
+        TritonGrpcClient::check_output_name(self).await
+    }
+}
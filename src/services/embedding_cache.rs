@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::models::Truncation;
+
+/// Raw model output for one input, before projection and normalization, so
+/// a hit serves requests with any `normalized` or `return_norms`.
+#[derive(Debug, Clone)]
+pub struct CachedEmbedding {
+    pub vector: Vec<f32>,
+    /// Real tokens of the input, counted towards usage on a hit
+    pub tokens: usize,
+}
+
+/// Least-recently-used cache of embeddings, keyed by a hash of the
+/// preprocessed text, task, model and truncation.
+pub struct EmbeddingCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<u64, (CachedEmbedding, u64)>,
+    /// Keys by the tick of their last use, oldest first
+    recency: BTreeMap<u64, u64>,
+    tick: u64,
+}
+
+impl CacheState {
+    fn touch(&mut self, key: u64, last_used: u64) -> u64 {
+        self.recency.remove(&last_used);
+        self.tick += 1;
+        self.recency.insert(self.tick, key);
+        self.tick
+    }
+}
+
+impl EmbeddingCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    pub fn key(text: &str, task: &str, model: &str, truncation: Truncation) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (text, task, model, truncation).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    pub fn get(&self, key: u64) -> Option<CachedEmbedding> {
+        let mut state = self.state.lock().unwrap();
+        let last_used = state.entries.get(&key)?.1;
+        let tick = state.touch(key, last_used);
+        let (embedding, used) = state.entries.get_mut(&key)?;
+        *used = tick;
+        Some(embedding.clone())
+    }
+
+    /// Adds an entry, evicting the least recently used one when full.
+    pub fn insert(&self, key: u64, embedding: CachedEmbedding) {
+        let mut state = self.state.lock().unwrap();
+        let last_used = state.entries.get(&key).map_or(0, |(_, used)| *used);
+        let tick = state.touch(key, last_used);
+        state.entries.insert(key, (embedding, tick));
+
+        while state.entries.len() > self.capacity {
+            let Some((_, oldest)) = state.recency.pop_first() else { break };
+            state.entries.remove(&oldest);
+        }
+    }
+
+    /// Empties the cache, returning how many entries were removed.
+    pub fn clear(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let cleared = state.entries.len();
+        state.entries.clear();
+        state.recency.clear();
+        cleared
+    }
+}
@@ -1,69 +1,1148 @@
 use crate::error::AppError;
-use crate::models::{EmbeddingModel, get_task_id};
-use crate::repositories::triton_client::TritonClient;
-use crate::services::tokenizer_service::TokenizerService;
-use crate::config::Settings;
-use tracing::info;
+use crate::metrics::Metrics;
+use crate::models::{
+    BatchShape, EmbeddingOptions, EmbeddingOutput, FlatEmbeddingOutput, FlatEmbeddings, InputWarning, OutputTensorInfo,
+    TaskWeight, get_task_id_checked,
+};
+use crate::repositories::EmbeddingRepository;
+use crate::services::embedding_cache::{CachedEmbedding, EmbeddingCache};
+use crate::services::preprocessing::preprocess_text;
+use crate::services::projection::Projection;
+use crate::services::tokenizer_service::{run_blocking, EncodedSequence, TokenizerService};
+use crate::config::{EmptyInputHandling, Settings};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Weight of the newest sample in the rolling Triton latency average
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// Known input run through the tokenizer and model by the startup consistency check
+const CONSISTENCY_CHECK_TEXT: &str = "Xin chào, bạn khỏe không? Machine learning là gì?";
+
+/// Embedding dimension assumed for memory estimates before Triton has answered
+const DEFAULT_EMBEDDING_DIM: usize = 1024;
 
 pub struct EmbeddingService {
-    client: TritonClient,
+    /// The `embedding_model_name` model, used unless a route matches
+    backend: EmbeddingBackend,
+    /// Backends for `embedding_model_routes`, by request model name
+    routes: HashMap<String, EmbeddingBackend>,
     tokenizer_service: TokenizerService,
+    adaptive_batch: Option<AdaptiveBatchSize>,
+    /// Dimension of the most recent embeddings, used for memory estimates
+    embedding_dim: AtomicUsize,
+    projection: Option<Projection>,
+    cache: Option<EmbeddingCache>,
+    /// Last readiness reported by Triton
+    ready: AtomicBool,
+    /// When `ready` was last refreshed, for `health_cache_ttl_secs`
+    ready_checked_at: Mutex<Option<Instant>>,
+}
+
+/// One Triton embedding model and its cached version
+struct EmbeddingBackend {
+    model_name: String,
+    client: Box<dyn EmbeddingRepository>,
+    model_version: RwLock<Option<String>>,
+}
+
+impl EmbeddingBackend {
+    fn new(model_name: String, client: Box<dyn EmbeddingRepository>) -> Self {
+        Self {
+            model_name,
+            client,
+            model_version: RwLock::new(None),
+        }
+    }
+}
+
+/// Batch size that grows by one while the rolling Triton latency stays
+/// under target and halves once it exceeds it, within `[1, max]`.
+struct AdaptiveBatchSize {
+    max: usize,
+    target_latency_ms: f64,
+    state: Mutex<AdaptiveBatchState>,
+}
+
+struct AdaptiveBatchState {
+    current: usize,
+    avg_latency_ms: Option<f64>,
+}
+
+impl AdaptiveBatchSize {
+    fn new(max: usize, target_latency_ms: u64) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            target_latency_ms: target_latency_ms as f64,
+            state: Mutex::new(AdaptiveBatchState { current: max, avg_latency_ms: None }),
+        }
+    }
+
+    fn current(&self) -> usize {
+        self.state.lock().unwrap().current
+    }
+
+    fn record(&self, latency_ms: f64) {
+        let mut state = self.state.lock().unwrap();
+        let avg = match state.avg_latency_ms {
+            Some(avg) => avg + LATENCY_SMOOTHING * (latency_ms - avg),
+            None => latency_ms,
+        };
+        state.avg_latency_ms = Some(avg);
+
+        let previous = state.current;
+        if avg > self.target_latency_ms {
+            state.current = (state.current / 2).max(1);
+        } else if state.current < self.max {
+            state.current += 1;
+        }
+
+        if state.current != previous {
+            info!("Adaptive batching: avg Triton latency {:.1}ms, batch size {} -> {}",
+                  avg, previous, state.current);
+        }
+    }
 }
 
 impl EmbeddingService {
-    pub fn new() -> Result<Self, AppError> {
+    pub fn new(
+        client: Box<dyn EmbeddingRepository>,
+        routes: HashMap<String, Box<dyn EmbeddingRepository>>,
+    ) -> Result<Self, AppError> {
         let settings = Settings::get();
-        let client = TritonClient::new(settings.embedding_model_name.clone())?;
         let tokenizer_service = TokenizerService::new();
+        let adaptive_batch = settings.adaptive_batching.then(|| {
+            AdaptiveBatchSize::new(
+                settings.embedding_client_max_batch,
+                settings.adaptive_batching_target_latency_ms,
+            )
+        });
+        let projection = settings.projection_matrix_path
+            .as_deref()
+            .map(Projection::load)
+            .transpose()?;
+
+        let routes = routes
+            .into_iter()
+            .map(|(name, client)| {
+                let model_name = settings.embedding_model_routes[&name].clone();
+                (name, EmbeddingBackend::new(model_name, client))
+            })
+            .collect();
 
         Ok(Self {
-            client,
+            backend: EmbeddingBackend::new(settings.embedding_model_name.clone(), client),
+            routes,
             tokenizer_service,
+            adaptive_batch,
+            embedding_dim: AtomicUsize::new(
+                projection.as_ref().map_or(DEFAULT_EMBEDDING_DIM, Projection::input_dim),
+            ),
+            projection,
+            cache: (settings.embedding_cache_size > 0).then(|| EmbeddingCache::new(settings.embedding_cache_size)),
+            ready: AtomicBool::new(false),
+            ready_checked_at: Mutex::new(None),
         })
     }
 
+    /// Embeds `texts` with the model selected by `model` (see `backend`).
     pub async fn create_embeddings(
         &self,
         texts: Vec<String>,
         task: &str,
-    ) -> Result<Vec<EmbeddingModel>, AppError> {
+        model: Option<&str>,
+        options: EmbeddingOptions,
+    ) -> Result<EmbeddingOutput, AppError> {
+        Ok(self.create_embeddings_flat(texts, task, model, options).await?.into())
+    }
+
+    /// Like `create_embeddings`, but returns every vector in one contiguous
+    /// row-major buffer with its `(rows, dim)` shape, without a separate
+    /// allocation per vector. Meant for callers that copy into a matrix.
+    pub async fn create_embeddings_flat(
+        &self,
+        texts: Vec<String>,
+        task: &str,
+        model: Option<&str>,
+        options: EmbeddingOptions,
+    ) -> Result<FlatEmbeddingOutput, AppError> {
+        if !Settings::get().dedup_inputs {
+            return self.embed_texts(texts, task, model, options).await;
+        }
+
+        let input_count = texts.len();
+        let (unique, positions) = dedup(texts);
+        if unique.len() == input_count {
+            return self.embed_texts(unique, task, model, options).await;
+        }
+
+        // Limits apply to the inputs as sent, not just the distinct ones
+        self.check_request(input_count, task)?;
+        info!("Embedding {} distinct texts for {} inputs", unique.len(), input_count);
+        let output = self.embed_texts(unique, task, model, options).await?;
+        Ok(expand_duplicates(output, &positions))
+    }
+
+    async fn embed_texts(
+        &self,
+        texts: Vec<String>,
+        task: &str,
+        model: Option<&str>,
+        options: EmbeddingOptions,
+    ) -> Result<FlatEmbeddingOutput, AppError> {
         if texts.is_empty() {
             return Err(AppError::Validation("Text input cannot be empty".to_string()));
         }
+        let backend = self.backend(model)?;
+        let task_id = self.check_request(texts.len(), task)?;
+        let settings = Settings::get();
+
+        let texts: Vec<String> = texts.into_iter().map(preprocess_text).collect();
+
+        // Blank (empty or whitespace-only) inputs are handled per
+        // `empty_input_vector` unless `allow_blank_inputs` sends them on
+        let is_blank = |text: &str| !settings.allow_blank_inputs && text.trim().is_empty();
+        let empty: Vec<usize> = texts
+            .iter()
+            .enumerate()
+            .filter(|(_, text)| is_blank(text))
+            .map(|(index, _)| index)
+            .collect();
+        if let Some(first) = empty.first() {
+            if settings.empty_input_vector == EmptyInputHandling::Error {
+                return Err(AppError::Validation(format!(
+                    "Input at index {} is empty or whitespace-only ({} such inputs in total)",
+                    first,
+                    empty.len()
+                )));
+            }
+        }
+
+        // Only non-blank inputs go to Triton; `indices` maps them back
+        let (indices, texts): (Vec<usize>, Vec<String>) = texts
+            .into_iter()
+            .enumerate()
+            .filter(|(_, text)| !is_blank(text))
+            .unzip();
+
+        // Cached inputs skip tokenization and Triton; `hits` are merged back
+        // into the Triton output by index below
+        let cache = self.cache.as_ref().filter(|_| !options.no_cache);
+        let cache_key = |text: &str| {
+            EmbeddingCache::key(text, task, &backend.model_name, options.truncation)
+        };
+        let mut hits: Vec<(usize, CachedEmbedding)> = Vec::new();
+        let (indices, texts): (Vec<usize>, Vec<String>) = match cache {
+            Some(cache) => indices
+                .into_iter()
+                .zip(texts)
+                .filter(|(index, text)| match cache.get(cache_key(text)) {
+                    Some(hit) => {
+                        hits.push((*index, hit));
+                        false
+                    }
+                    None => true,
+                })
+                .unzip(),
+            None => (indices, texts),
+        };
+        if !hits.is_empty() {
+            info!("Embedding cache: {} hits, {} misses", hits.len(), texts.len());
+        }
+
+        info!("Generating embeddings for {} texts with task '{}' on '{}'", texts.len(), task, backend.model_name);
+
+        let started = Instant::now();
+        let tokenizer_service = self.tokenizer_service;
+        let (texts, tokenized) = run_blocking(move || {
+            let tokenized = tokenizer_service.tokenize_for_embedding(&texts, options.truncation);
+            (texts, tokenized)
+        })
+        .await?;
+        let tokenized = tokenized?;
+        let tokenize_ms = started.elapsed().as_millis() as u64;
+        info!(tokenize_ms, "Tokenized {} inputs", texts.len());
+        // `positions` locates each sequence's text in `texts`, for filling the cache
+        let CollectedSequences { sequences, mut indices, positions, failed_inputs } =
+            collect_sequences(indices, tokenized, options.isolate_errors)?;
+        // Counted before batching, so padding added per batch is excluded
+        let mut prompt_tokens: usize = sequences.iter().map(TokenizerService::token_count).sum();
+
+        let (mut data, mut dim, output_tensors, batch_shapes) =
+            self.run_batches(backend, &sequences, task_id, options).await?;
+
+        check_finite(&mut data, dim, settings.sanitize_output)?;
+
+        if let Some(cache) = cache {
+            for ((sequence, position), vector) in sequences.iter().zip(&positions).zip(data.chunks(dim.max(1))) {
+                cache.insert(cache_key(&texts[*position]), CachedEmbedding {
+                    vector: vector.to_vec(),
+                    tokens: TokenizerService::token_count(sequence),
+                });
+            }
+        }
+        if !hits.is_empty() {
+            prompt_tokens += hits.iter().map(|(_, hit)| hit.tokens).sum::<usize>();
+            (data, dim, indices) = merge_cached(data, dim, indices, hits)?;
+        }
 
-        let task_id = get_task_id(task);
-        info!("Generating embeddings for {} texts with task '{}'", texts.len(), task);
+        let mut embeddings = self.finish(data, dim, indices, options)?;
+
+        let mut skipped_indices = Vec::new();
+        match settings.empty_input_vector {
+            EmptyInputHandling::Zero if !empty.is_empty() => {
+                if embeddings.shape.1 == 0 {
+                    embeddings.shape.1 = self.output_dim();
+                }
+                embeddings.insert_zero_rows(&empty);
+            }
+            EmptyInputHandling::Skip => skipped_indices = empty,
+            _ => {}
+        }
 
+        info!("Successfully generated {} embeddings", embeddings.shape.0);
+        Ok(FlatEmbeddingOutput {
+            embeddings,
+            output_tensors,
+            batch_shapes,
+            skipped_indices,
+            prompt_tokens,
+            failed_inputs,
+        })
+    }
+
+    /// Embeds inputs the client already tokenized, sending each id sequence
+    /// to the model as given instead of tokenizing text. Preprocessing, blank
+    /// input handling and the embedding cache apply to text inputs only.
+    pub async fn create_embeddings_from_tokens(
+        &self,
+        token_ids: Vec<Vec<u32>>,
+        task: &str,
+        model: Option<&str>,
+        options: EmbeddingOptions,
+    ) -> Result<EmbeddingOutput, AppError> {
+        if token_ids.is_empty() {
+            return Err(AppError::Validation("Token id input cannot be empty".to_string()));
+        }
+        let backend = self.backend(model)?;
+        let task_id = self.check_request(token_ids.len(), task)?;
+
+        info!("Generating embeddings for {} token id inputs with task '{}' on '{}'", token_ids.len(), task, backend.model_name);
+        let indices = (0..token_ids.len()).collect();
+        let encoded = self.tokenizer_service.encode_token_ids(token_ids, options.truncation)?;
+        let CollectedSequences { sequences, indices, failed_inputs, .. } =
+            collect_sequences(indices, encoded, options.isolate_errors)?;
+        let prompt_tokens = sequences.iter().map(TokenizerService::token_count).sum();
+
+        let (mut data, dim, output_tensors, batch_shapes) =
+            self.run_batches(backend, &sequences, task_id, options).await?;
+        check_finite(&mut data, dim, Settings::get().sanitize_output)?;
+        let embeddings = self.finish(data, dim, indices, options)?;
+
+        info!("Successfully generated {} embeddings", embeddings.shape.0);
+        Ok(FlatEmbeddingOutput {
+            embeddings,
+            output_tensors,
+            batch_shapes,
+            skipped_indices: Vec::new(),
+            prompt_tokens,
+            failed_inputs,
+        }
+        .into())
+    }
+
+    /// Checks the input count and task against the configured limits,
+    /// returning the task's id.
+    fn check_request(&self, inputs: usize, task: &str) -> Result<i64, AppError> {
+        let settings = Settings::get();
+
+        // Client-facing cap; requests under it are split into Triton batches
+        // of embedding_client_max_batch in `run_batches`
+        if let Some(limit) = settings.max_inputs_per_request {
+            if inputs > limit {
+                return Err(AppError::Validation(format!(
+                    "Too many inputs: {} provided, maximum is {} per request",
+                    inputs,
+                    limit
+                )));
+            }
+        }
+
+        if let Some(limit) = settings.max_response_items {
+            self.check_response_size(inputs, limit)?;
+        }
+
+        let task_id = get_task_id_checked(task)?;
+
+        if let Some(enabled_tasks) = &settings.enabled_tasks {
+            if !enabled_tasks.iter().any(|t| t == task) {
+                return Err(AppError::Validation(format!(
+                    "Task '{}' is not enabled. Enabled tasks: {}",
+                    task,
+                    enabled_tasks.join(", ")
+                )));
+            }
+        }
+
+        Ok(task_id)
+    }
+
+    /// Pads the sequences into batches and runs them through the model,
+    /// returning the row-major output, its dimension, and per-batch details.
+    async fn run_batches(
+        &self,
+        backend: &EmbeddingBackend,
+        sequences: &[EncodedSequence],
+        task_id: i64,
+        options: EmbeddingOptions,
+    ) -> Result<(Vec<f32>, usize, Vec<OutputTensorInfo>, Vec<BatchShape>), AppError> {
         let settings = Settings::get();
-        let max_batch = settings.embedding_client_max_batch;
+        if let Some(limit) = settings.max_request_memory_bytes {
+            self.check_memory_estimate(sequences, limit)?;
+        }
+
+        let max_batch = match &self.adaptive_batch {
+            Some(adaptive) => adaptive.current(),
+            None => settings.embedding_client_max_batch,
+        };
+
+        let mut data: Vec<f32> = Vec::new();
+        let mut dim = 0;
+        let mut output_tensors = Vec::new();
+        let mut batch_shapes = Vec::new();
+
+        // Sequences in the order they are batched. Sorting by length puts
+        // similar lengths in the same batch, so less of each batch is padding
+        let mut order: Vec<usize> = (0..sequences.len()).collect();
+        if settings.sort_batches_by_length {
+            order.sort_by_key(|&i| sequences[i].0.len());
+        }
 
-        let mut all_embeddings = Vec::new();
+        let planned = plan_batches(
+            sequences,
+            &order,
+            max_batch,
+            settings.max_batch_tokens,
+            settings.fixed_sequence_length,
+        );
 
-        for chunk in texts.chunks(max_batch) {
-            let chunk_vec: Vec<String> = chunk.to_vec();
-            let (input_ids, attention_mask) = self.tokenizer_service
-                .tokenize_for_embedding(&chunk_vec)?;
+        for range in planned {
+            let batch = &order[range];
+            let (input_ids, attention_mask) =
+                TokenizerService::pad_batch(batch.iter().map(|&i| &sequences[i]));
+            let padded_length = input_ids.first().map_or(0, Vec::len);
+            Metrics::get()
+                .embedding_sequence_length
+                .observe(padded_length as u64);
+            batch_shapes.push(BatchShape {
+                batch_size: batch.len(),
+                padded_length,
+                tokens: batch.iter().map(|&i| TokenizerService::token_count(&sequences[i])).sum(),
+            });
 
-            let embeddings = self.client
-                .get_embeddings(&input_ids, &attention_mask, task_id)
+            let started = Instant::now();
+            let (batch_data, batch_dim, tensor_info) = backend.client
+                .generate_embeddings(&input_ids, &attention_mask, task_id, options.timeout)
                 .await?;
 
-            all_embeddings.extend(embeddings);
+            if let Some(adaptive) = &self.adaptive_batch {
+                adaptive.record(started.elapsed().as_secs_f64() * 1000.0);
+            }
+
+            if data.is_empty() {
+                dim = batch_dim;
+                data = batch_data;
+            } else if batch_dim != dim {
+                return Err(AppError::Inference(format!(
+                    "Triton returned {}-dimensional embeddings after {}-dimensional ones in the same request",
+                    batch_dim, dim
+                )));
+            } else {
+                data.extend_from_slice(&batch_data);
+            }
+            self.embedding_dim.store(dim, Ordering::Relaxed);
+            output_tensors.push(tensor_info);
         }
 
-        let embedding_models: Vec<EmbeddingModel> = all_embeddings
-            .into_iter()
-            .enumerate()
-            .map(|(index, vector)| EmbeddingModel { vector, index })
-            .collect();
+        if settings.sort_batches_by_length {
+            data = restore_order(data, dim, &order);
+        }
+
+        Ok((data, dim, output_tensors, batch_shapes))
+    }
+
+    /// Applies the projection, norms and normalization to raw model output.
+    fn finish(
+        &self,
+        mut data: Vec<f32>,
+        mut dim: usize,
+        indices: Vec<usize>,
+        options: EmbeddingOptions,
+    ) -> Result<FlatEmbeddings, AppError> {
+        if let Some(projection) = &self.projection {
+            let mut projected = Vec::with_capacity(indices.len() * projection.output_dim());
+            for vector in data.chunks(dim.max(1)) {
+                projected.extend(projection.apply(vector)?);
+            }
+            data = projected;
+            dim = projection.output_dim();
+        }
+
+        let norms = options.return_norms.then(|| row_norms(&data, dim, indices.len()));
+
+        // After any dimension reduction, so the returned vector has unit norm
+        if options.normalize && dim > 0 {
+            data.chunks_mut(dim).for_each(l2_normalize);
+        }
+
+        Ok(FlatEmbeddings {
+            data,
+            shape: (indices.len(), dim),
+            indices,
+            norms,
+        })
+    }
+
+    /// Embeds `texts` once per task and combines the normalized per-task
+    /// embeddings into their weighted average.
+    pub async fn create_weighted_embeddings(
+        &self,
+        texts: Vec<String>,
+        tasks: &[TaskWeight],
+        model: Option<&str>,
+        options: EmbeddingOptions,
+    ) -> Result<EmbeddingOutput, AppError> {
+        if tasks.is_empty() {
+            return Err(AppError::Validation("tasks cannot be empty".to_string()));
+        }
+        if let Some(task) = tasks.iter().find(|task| !task.weight.is_finite()) {
+            return Err(AppError::Validation(format!("Weight for task '{}' must be finite", task.task)));
+        }
+        let total_weight: f32 = tasks.iter().map(|task| task.weight).sum();
+        if total_weight <= 0.0 {
+            return Err(AppError::Validation(format!(
+                "Task weights must sum to a positive value, got {}",
+                total_weight
+            )));
+        }
+        for task in tasks {
+            get_task_id_checked(&task.task)?;
+        }
+
+        // Per-task vectors are always normalized so each task contributes by weight alone
+        let per_task_options = EmbeddingOptions { normalize: true, return_norms: false, ..options };
+        let outputs = futures_util::future::try_join_all(
+            tasks.iter().map(|task| self.create_embeddings_flat(texts.clone(), &task.task, model, per_task_options)),
+        )
+        .await?;
+
+        let mut outputs = outputs.into_iter().zip(tasks);
+        let (mut combined, first) = outputs.next().expect("tasks is not empty");
+        combined.embeddings.data.iter_mut().for_each(|v| *v *= first.weight);
+
+        for (output, task) in outputs {
+            debug_assert_eq!(combined.embeddings.indices, output.embeddings.indices);
+            combined.embeddings.data
+                .iter_mut()
+                .zip(output.embeddings.data)
+                .for_each(|(v, other)| *v += task.weight * other);
+            combined.output_tensors.extend(output.output_tensors);
+            combined.batch_shapes.extend(output.batch_shapes);
+            combined.prompt_tokens += output.prompt_tokens;
+        }
+
+        combined.embeddings.data.iter_mut().for_each(|v| *v /= total_weight);
+        let (rows, dim) = combined.embeddings.shape;
+        if options.return_norms {
+            combined.embeddings.norms = Some(row_norms(&combined.embeddings.data, dim, rows));
+        }
+        if options.normalize && dim > 0 {
+            combined.embeddings.data.chunks_mut(dim).for_each(l2_normalize);
+        }
+
+        Ok(combined.into())
+    }
+
+    /// Backend serving the request `model`: its route when one is configured,
+    /// otherwise `embedding_model_name`. Without routes any name is accepted,
+    /// as before routing existed; with routes, unknown names are rejected.
+    fn backend(&self, model: Option<&str>) -> Result<&EmbeddingBackend, AppError> {
+        let Some(model) = model else {
+            return Ok(&self.backend);
+        };
+        if let Some(route) = self.routes.get(model) {
+            return Ok(route);
+        }
+        if self.routes.is_empty() || model == self.backend.model_name {
+            return Ok(&self.backend);
+        }
 
-        info!("Successfully generated {} embeddings", embedding_models.len());
-        Ok(embedding_models)
+        Err(AppError::Validation(format!(
+            "Unknown model '{}'. Available models: {}",
+            model,
+            self.model_names().join(", ")
+        )))
     }
 
+    /// Triton model that serves the request `model`, rejecting unknown names
+    /// as `create_embeddings` would.
+    pub fn resolve_model(&self, model: Option<&str>) -> Result<&str, AppError> {
+        self.backend(model).map(|backend| backend.model_name.as_str())
+    }
+
+    /// Model names accepted in requests: `embedding_model_name` and every
+    /// configured route, sorted.
+    pub fn model_names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.routes.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        if !self.routes.contains_key(&self.backend.model_name) {
+            names.insert(0, &self.backend.model_name);
+        }
+        names
+    }
+
+    /// Dimension of returned embeddings: the projection's output when one is
+    /// configured, otherwise the last seen model dimension.
+    fn output_dim(&self) -> usize {
+        match &self.projection {
+            Some(projection) => projection.output_dim(),
+            None => self.embedding_dim.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Rejects the request when its response would hold more than `limit`
+    /// embedding values (inputs x output dimension).
+    fn check_response_size(&self, inputs: usize, limit: usize) -> Result<(), AppError> {
+        let dim = self.output_dim();
+        let items = inputs.saturating_mul(dim);
+
+        if items > limit {
+            let max_inputs = (limit / dim.max(1)).max(1);
+            return Err(AppError::Validation(format!(
+                "Response too large: {} inputs x {} dimensions = {} values exceeds max_response_items = {}; split the request into batches of at most {} inputs",
+                inputs, dim, items, limit, max_inputs
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Rejects the request when `batch * longest sequence * dim * 4 bytes`
+    /// exceeds the configured memory limit.
+    fn check_memory_estimate(&self, sequences: &[EncodedSequence], limit: u64) -> Result<(), AppError> {
+        let settings = Settings::get();
+        let longest = settings.fixed_sequence_length.unwrap_or_else(|| {
+            sequences.iter().map(|(ids, _)| ids.len()).max().unwrap_or(0)
+        });
+        let dim = self.embedding_dim.load(Ordering::Relaxed);
+        let estimate = (sequences.len() as u64)
+            .saturating_mul(longest as u64)
+            .saturating_mul(dim as u64)
+            .saturating_mul(std::mem::size_of::<f32>() as u64);
+
+        if estimate > limit {
+            warn!("Rejecting embedding request: estimated {} bytes ({} inputs x {} tokens x {} dims) exceeds max_request_memory_bytes = {}",
+                  estimate, sequences.len(), longest, dim, limit);
+            return Err(AppError::Overloaded(format!(
+                "Request too large: estimated memory {} bytes exceeds limit of {} bytes; split the input into smaller requests",
+                estimate, limit
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Triton model version serving the request `model`, fetched once and cached.
+    pub async fn model_version(&self, model: Option<&str>) -> Option<String> {
+        if !Settings::get().model_version_header {
+            return None;
+        }
+        let backend = self.backend(model).ok()?;
+
+        if let Some(version) = backend.model_version.read().unwrap().clone() {
+            return Some(version);
+        }
+
+        match backend.client.get_model_version().await {
+            Ok(Some(version)) => {
+                *backend.model_version.write().unwrap() = Some(version.clone());
+                Some(version)
+            }
+            Ok(None) => {
+                warn!("Failed to fetch model version: Triton reported no model versions");
+                None
+            }
+            Err(e) => {
+                warn!("Failed to fetch model version: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Clears this service's cached values, returning how many were cleared.
+    pub fn clear_caches(&self) -> usize {
+        std::iter::once(&self.backend)
+            .chain(self.routes.values())
+            .filter(|backend| backend.model_version.write().unwrap().take().is_some())
+            .count()
+            + usize::from(self.ready_checked_at.lock().unwrap().take().is_some())
+            + self.clear_embedding_cache()
+    }
+
+    /// Empties the embedding cache, returning how many embeddings it held.
+    pub fn clear_embedding_cache(&self) -> usize {
+        self.cache.as_ref().map_or(0, EmbeddingCache::clear)
+    }
+
+    /// Fails with `NotReady` when this service's Triton model can't serve.
+    pub async fn ensure_ready(&self) -> Result<(), AppError> {
+        if self.is_ready().await.unwrap_or(false) {
+            Ok(())
+        } else {
+            Err(AppError::NotReady(format!(
+                "Embedding model '{}' is not ready, try again later",
+                Settings::get().embedding_model_name
+            )))
+        }
+    }
+
+    /// Embeds a known string end to end, failing when Triton rejects the
+    /// tokenized input or the model's output dimension doesn't match the
+    /// configuration (a sign of a tokenizer meant for another model).
+    pub async fn check_consistency(&self) -> Result<usize, AppError> {
+        let settings = Settings::get();
+        let task = settings.enabled_tasks
+            .as_ref()
+            .and_then(|tasks| tasks.first())
+            .map_or("retrieval.query", String::as_str);
+
+        let output = self
+            .create_embeddings(
+                vec![CONSISTENCY_CHECK_TEXT.to_string()],
+                task,
+                None,
+                EmbeddingOptions { no_cache: true, ..Default::default() },
+            )
+            .await?;
+        if output.embeddings.len() != 1 {
+            return Err(AppError::Internal(format!(
+                "Consistency check expected 1 embedding, got {}",
+                output.embeddings.len()
+            )));
+        }
+
+        let model_dim = self.embedding_dim.load(Ordering::Relaxed);
+        if let Some(expected) = settings.expected_embedding_dim {
+            if model_dim != expected {
+                return Err(AppError::Internal(format!(
+                    "Embedding model returned dimension {}, but EXPECTED_EMBEDDING_DIM is {}; check that the tokenizer and model match",
+                    model_dim, expected
+                )));
+            }
+        }
+        Ok(model_dim)
+    }
+
+    /// Checks that every routed Triton model exposes the output tensor this
+    /// service reads.
+    pub async fn check_output_tensor(&self) -> Result<(), AppError> {
+        for backend in std::iter::once(&self.backend).chain(self.routes.values()) {
+            backend.client.check_output_name().await?;
+        }
+        Ok(())
+    }
+
+    /// Live readiness of the Triton model serving the request `model`.
+    pub async fn is_model_ready(&self, model: &str) -> bool {
+        match self.backend(Some(model)) {
+            Ok(backend) => backend.client.is_ready().await.unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    /// Model readiness: the background poller's last result when
+    /// `readiness_poll_secs` is set, the last live result while it is younger
+    /// than `health_cache_ttl_secs`, otherwise a live query to Triton.
     pub async fn is_ready(&self) -> Result<bool, AppError> {
-        let live = self.client.is_server_live().await?;
-        let ready = self.client.is_model_ready().await?;
-        Ok(live && ready)
+        let settings = Settings::get();
+        if settings.readiness_poll_secs.is_some() {
+            return Ok(self.ready.load(Ordering::Relaxed));
+        }
+
+        if let Some(ttl) = settings.health_cache_ttl_secs {
+            let fresh = self.ready_checked_at
+                .lock()
+                .unwrap()
+                .is_some_and(|checked_at| checked_at.elapsed() < Duration::from_secs(ttl));
+            if fresh {
+                return Ok(self.ready.load(Ordering::Relaxed));
+            }
+        }
+
+        self.check_ready().await
+    }
+
+    /// Queries Triton for readiness and remembers the result, logging when it
+    /// changes.
+    pub async fn check_ready(&self) -> Result<bool, AppError> {
+        let result = self.backend.client.is_ready().await;
+        let ready = *result.as_ref().unwrap_or(&false);
+        *self.ready_checked_at.lock().unwrap() = Some(Instant::now());
+        if self.ready.swap(ready, Ordering::Relaxed) != ready {
+            info!(
+                "Embedding model '{}' is now {}",
+                Settings::get().embedding_model_name,
+                if ready { "ready" } else { "not ready" }
+            );
+        }
+        result
+    }
+}
+
+fn l2_norm(vector: &[f32]) -> f32 {
+    vector.iter().map(|v| v * v).sum::<f32>().sqrt()
+}
+
+/// Scales a vector to unit L2 norm, leaving all-zero vectors untouched.
+fn l2_normalize(vector: &mut [f32]) {
+    let norm = l2_norm(vector);
+    if norm > 0.0 {
+        vector.iter_mut().for_each(|v| *v /= norm);
+    }
+}
+
+/// L2 norm of each of the `rows` rows of a row-major buffer.
+fn row_norms(data: &[f32], dim: usize, rows: usize) -> Vec<f32> {
+    if dim == 0 {
+        return vec![0.0; rows];
+    }
+    data.chunks(dim).map(l2_norm).collect()
+}
+
+/// Fails on NaN or infinite values in model output, or replaces them (NaN
+/// with 0, infinities with the largest finite value) when `sanitize` is set.
+fn check_finite(data: &mut [f32], dim: usize, sanitize: bool) -> Result<(), AppError> {
+    let mut replaced = 0;
+
+    for (position, value) in data.iter_mut().enumerate().filter(|(_, value)| !value.is_finite()) {
+        if !sanitize {
+            return Err(AppError::Inference(format!(
+                "Model returned a non-finite value ({}) in embedding {}",
+                value,
+                position / dim.max(1)
+            )));
+        }
+        *value = if value.is_nan() { 0.0 } else { value.signum() * f32::MAX };
+        replaced += 1;
+    }
+
+    if replaced > 0 {
+        warn!("Sanitized {} non-finite values in model output", replaced);
+    }
+    Ok(())
+}
+
+/// Distinct texts in first-seen order, and the position among them of each
+/// input text.
+fn dedup(texts: Vec<String>) -> (Vec<String>, Vec<usize>) {
+    let mut seen: HashMap<String, usize> = HashMap::with_capacity(texts.len());
+    let mut unique = Vec::new();
+    let positions = texts
+        .into_iter()
+        .map(|text| {
+            *seen.entry(text).or_insert_with_key(|text| {
+                unique.push(text.clone());
+                unique.len() - 1
+            })
+        })
+        .collect();
+    (unique, positions)
+}
+
+/// Maps output for the distinct texts back to the original inputs, where
+/// `positions` gives each input's distinct text.
+fn expand_duplicates(output: FlatEmbeddingOutput, positions: &[usize]) -> FlatEmbeddingOutput {
+    let FlatEmbeddings { data, shape: (_, dim), indices, norms } = output.embeddings;
+    let mut row_of = vec![None; positions.len()];
+    for (row, &position) in indices.iter().enumerate() {
+        row_of[position] = Some(row);
+    }
+
+    let mut embeddings = FlatEmbeddings {
+        data: Vec::with_capacity(positions.len() * dim),
+        shape: (0, dim),
+        indices: Vec::with_capacity(positions.len()),
+        norms: norms.as_ref().map(|_| Vec::with_capacity(positions.len())),
+    };
+    for (index, &position) in positions.iter().enumerate() {
+        let Some(row) = row_of[position] else { continue };
+        embeddings.data.extend_from_slice(&data[row * dim..(row + 1) * dim]);
+        embeddings.indices.push(index);
+        if let (Some(expanded), Some(norms)) = (&mut embeddings.norms, &norms) {
+            expanded.push(norms[row]);
+        }
+    }
+    embeddings.shape.0 = embeddings.indices.len();
+
+    let skipped_indices = (0..positions.len())
+        .filter(|&index| output.skipped_indices.contains(&positions[index]))
+        .collect();
+    let failed_inputs = positions
+        .iter()
+        .enumerate()
+        .filter_map(|(index, position)| {
+            output.failed_inputs
+                .iter()
+                .find(|warning| warning.index == *position)
+                .map(|warning| InputWarning { index, message: warning.message.clone() })
+        })
+        .collect();
+
+    FlatEmbeddingOutput {
+        embeddings,
+        skipped_indices,
+        failed_inputs,
+        ..output
+    }
+}
+
+/// Successfully tokenized inputs of a request, with the ones that failed
+struct CollectedSequences {
+    sequences: Vec<EncodedSequence>,
+    /// Input index of each sequence
+    indices: Vec<usize>,
+    /// Position of each sequence among the tokenizer results
+    positions: Vec<usize>,
+    failed_inputs: Vec<InputWarning>,
+}
+
+/// Splits per-input tokenizer results into the sequences to embed,
+/// collecting failed inputs as warnings when `isolate_errors` is set.
+fn collect_sequences(
+    indices: Vec<usize>,
+    tokenized: Vec<Result<EncodedSequence, AppError>>,
+    isolate_errors: bool,
+) -> Result<CollectedSequences, AppError> {
+    let mut sequences = Vec::with_capacity(tokenized.len());
+    let mut tokenized_indices = Vec::with_capacity(tokenized.len());
+    let mut positions = Vec::with_capacity(tokenized.len());
+    let mut failed_inputs = Vec::new();
+    for (position, (index, result)) in indices.into_iter().zip(tokenized).enumerate() {
+        match result {
+            Ok(sequence) => {
+                sequences.push(sequence);
+                tokenized_indices.push(index);
+                positions.push(position);
+            }
+            Err(e) if isolate_errors => {
+                warn!("Skipping input {}: {}", index, e);
+                failed_inputs.push(InputWarning { index, message: e.to_string() });
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(CollectedSequences {
+        sequences,
+        indices: tokenized_indices,
+        positions,
+        failed_inputs,
+    })
+}
+
+/// Merges cache hits into the Triton output rows, ordered by input index.
+fn merge_cached(
+    data: Vec<f32>,
+    dim: usize,
+    indices: Vec<usize>,
+    hits: Vec<(usize, CachedEmbedding)>,
+) -> Result<(Vec<f32>, usize, Vec<usize>), AppError> {
+    let dim = if indices.is_empty() { hits[0].1.vector.len() } else { dim };
+    if let Some((index, hit)) = hits.iter().find(|(_, hit)| hit.vector.len() != dim) {
+        return Err(AppError::Inference(format!(
+            "Cached embedding for input {} has dimension {}, but the model returned {}",
+            index,
+            hit.vector.len(),
+            dim
+        )));
+    }
+
+    let mut rows: Vec<(usize, &[f32])> = indices
+        .iter()
+        .copied()
+        .zip(data.chunks(dim.max(1)))
+        .chain(hits.iter().map(|(index, hit)| (*index, hit.vector.as_slice())))
+        .collect();
+    rows.sort_by_key(|(index, _)| *index);
+
+    let indices = rows.iter().map(|(index, _)| *index).collect();
+    let data = rows.iter().flat_map(|(_, row)| row.iter().copied()).collect();
+    Ok((data, dim, indices))
+}
+
+/// Moves output rows produced in batching `order` back to sequence order.
+fn restore_order(data: Vec<f32>, dim: usize, order: &[usize]) -> Vec<f32> {
+    if dim == 0 {
+        return data;
+    }
+    let mut restored = vec![0.0; data.len()];
+    for (row, &position) in data.chunks(dim).zip(order) {
+        restored[position * dim..(position + 1) * dim].copy_from_slice(row);
+    }
+    restored
+}
+
+/// Groups the sequences, taken in `order`, into consecutive batches of at
+/// most `max_batch` items. The returned ranges index into `order`.
+///
+/// When `max_batch_tokens` is set, a batch is also closed early once its
+/// padded size (`items * longest sequence`) would exceed the budget. A single
+/// sequence longer than the budget is sent on its own. With a fixed sequence
+/// length every sequence counts as that length.
+fn plan_batches(
+    sequences: &[EncodedSequence],
+    order: &[usize],
+    max_batch: usize,
+    max_batch_tokens: Option<usize>,
+    fixed_sequence_length: Option<usize>,
+) -> Vec<Range<usize>> {
+    let max_batch = max_batch.max(1);
+    let mut batches = Vec::new();
+    let mut start = 0;
+    let mut longest = 0;
+
+    for (i, &sequence) in order.iter().enumerate() {
+        let len = fixed_sequence_length.unwrap_or(sequences[sequence].0.len());
+        let count = i - start;
+
+        if count > 0 {
+            let over_count = count >= max_batch;
+            let over_budget = max_batch_tokens
+                .is_some_and(|budget| (count + 1) * longest.max(len) > budget);
+
+            if over_count || over_budget {
+                batches.push(start..i);
+                start = i;
+                longest = 0;
+            }
+        }
+
+        if let Some(budget) = max_batch_tokens {
+            if len > budget {
+                warn!("Sequence {} has {} tokens, exceeding max_batch_tokens = {}", sequence, len, budget);
+            }
+        }
+
+        longest = longest.max(len);
+    }
+
+    if start < order.len() {
+        batches.push(start..order.len());
+    }
+
+    batches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_maps_repeated_text_to_identical_vectors() {
+        let texts = ["a", "b", "a", "c", "a"].map(String::from).to_vec();
+        let (unique, positions) = dedup(texts);
+        assert_eq!(unique, ["a", "b", "c"]);
+        assert_eq!(positions, [0, 1, 0, 2, 0]);
+
+        let output = FlatEmbeddingOutput {
+            embeddings: FlatEmbeddings {
+                data: vec![1.0, 0.0, 0.0, 1.0, 0.5, 0.5],
+                shape: (3, 2),
+                indices: vec![0, 1, 2],
+                norms: None,
+            },
+            output_tensors: Vec::new(),
+            batch_shapes: Vec::new(),
+            skipped_indices: Vec::new(),
+            prompt_tokens: 3,
+            failed_inputs: Vec::new(),
+        };
+        let expanded = expand_duplicates(output, &positions).embeddings;
+
+        assert_eq!(expanded.shape, (5, 2));
+        assert_eq!(expanded.indices, [0, 1, 2, 3, 4]);
+        let rows: Vec<&[f32]> = expanded.data.chunks(2).collect();
+        for index in [0, 2, 4] {
+            assert_eq!(rows[index], [1.0, 0.0]);
+        }
+        assert_eq!(rows[1], [0.0, 1.0]);
+        assert_eq!(rows[3], [0.5, 0.5]);
+    }
+
+    #[test]
+    fn inputs_over_the_batch_size_are_split_into_several_batches() {
+        let sequences: Vec<EncodedSequence> = (0..20).map(|_| (vec![1; 4], vec![1; 4])).collect();
+        let order: Vec<usize> = (0..sequences.len()).collect();
+
+        let batches = plan_batches(&sequences, &order, 8, None, None);
+
+        assert_eq!(batches, [0..8, 8..16, 16..20]);
+    }
+
+    /// Compares handing a large batch to a caller that wants one matrix as
+    /// `FlatEmbeddings` against splitting it into `Vec<EmbeddingModel>` first.
+    /// Run with `cargo test --release -- --ignored --nocapture flat_embeddings`.
+    #[test]
+    #[ignore = "timing benchmark"]
+    fn flat_embeddings_vs_per_vector_timing() {
+        const ROWS: usize = 4096;
+        const DIM: usize = 1024;
+        const ITERATIONS: u32 = 20;
+
+        let output = FlatEmbeddingOutput {
+            embeddings: FlatEmbeddings {
+                data: (0..ROWS * DIM).map(|i| i as f32).collect(),
+                shape: (ROWS, DIM),
+                indices: (0..ROWS).collect(),
+                norms: None,
+            },
+            output_tensors: Vec::new(),
+            batch_shapes: Vec::new(),
+            skipped_indices: Vec::new(),
+            prompt_tokens: ROWS,
+            failed_inputs: Vec::new(),
+        };
+
+        let (mut flat, mut per_vector) = (Duration::ZERO, Duration::ZERO);
+        for _ in 0..ITERATIONS {
+            let input = output.clone();
+            let started = Instant::now();
+            let matrix = input.embeddings.data;
+            flat += started.elapsed();
+            assert_eq!(matrix.len(), ROWS * DIM);
+
+            let input = output.clone();
+            let started = Instant::now();
+            let split: EmbeddingOutput = input.into();
+            let mut matrix = Vec::with_capacity(ROWS * DIM);
+            for embedding in &split.embeddings {
+                matrix.extend_from_slice(&embedding.vector);
+            }
+            per_vector += started.elapsed();
+            assert_eq!(matrix.len(), ROWS * DIM);
+        }
+
+        println!(
+            "{} x {} floats, mean of {} runs: flat {:?}, per-vector {:?}",
+            ROWS,
+            DIM,
+            ITERATIONS,
+            flat / ITERATIONS,
+            per_vector / ITERATIONS
+        );
     }
 }
@@ -0,0 +1,182 @@
+use crate::config::Settings;
+use crate::error::AppError;
+use crate::models::{EmbeddingOptions, RerankOptions};
+use crate::services::embedding_service::EmbeddingService;
+use crate::services::reranking_service::RerankingService;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+
+/// How often the lifecycle monitor re-checks Triton readiness when
+/// `readiness_poll_secs` is unset
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Text sent through both models during warmup
+const WARMUP_TEXT: &str = "Machine learning là một nhánh của trí tuệ nhân tạo.";
+
+/// Where the process is in its startup and serving lifecycle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LifecycleState {
+    /// Waiting for Triton to report both models ready
+    Starting,
+    /// Triton is ready and warmup requests are running
+    Warming,
+    /// Warmed up and Triton ready; serving traffic
+    Ready,
+    /// Was ready, but Triton has since failed a readiness check
+    Degraded,
+}
+
+impl LifecycleState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            1 => LifecycleState::Warming,
+            2 => LifecycleState::Ready,
+            3 => LifecycleState::Degraded,
+            _ => LifecycleState::Starting,
+        }
+    }
+}
+
+/// Current lifecycle state, shared between the monitor and health handlers
+pub struct Lifecycle {
+    state: AtomicU8,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self { state: AtomicU8::new(LifecycleState::Starting as u8) }
+    }
+
+    pub fn get(&self) -> LifecycleState {
+        LifecycleState::from_u8(self.state.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, state: LifecycleState) {
+        let previous = LifecycleState::from_u8(self.state.swap(state as u8, Ordering::Relaxed));
+        if previous != state {
+            info!("Lifecycle state: {:?} -> {:?}", previous, state);
+        }
+    }
+}
+
+/// Moves the lifecycle through `starting` -> `warming` -> `ready`, then keeps
+/// polling Triton: a failed check marks the process `degraded`, and recovery
+/// runs warmup again before returning to `ready`. Runs until the process exits.
+/// Each check also refreshes the services' cached readiness.
+///
+/// Each model's tokenizer/model consistency check runs once that model is
+/// first ready, and the process doesn't report `ready` before both have run.
+pub async fn monitor(
+    lifecycle: Arc<Lifecycle>,
+    embedding_service: Arc<EmbeddingService>,
+    reranking_service: Arc<RerankingService>,
+) {
+    let poll_interval = Settings::get()
+        .readiness_poll_secs
+        .map_or(DEFAULT_POLL_INTERVAL, |secs| Duration::from_secs(secs.max(1)));
+
+    let mut embedding_checked = false;
+    let mut reranking_checked = false;
+
+    loop {
+        let (embedding_ready, reranking_ready) = tokio::join!(
+            embedding_service.check_ready(),
+            reranking_service.check_ready(),
+        );
+        let (embedding_ready, reranking_ready) = (embedding_ready.unwrap_or(false), reranking_ready.unwrap_or(false));
+
+        // Catch a tokenizer that doesn't match its model before serving traffic
+        if embedding_ready && !embedding_checked {
+            let result = embedding_service.check_consistency().await.map(|dim| {
+                info!("Embedding model output dimension is {}", dim);
+            });
+            embedding_checked = settle_consistency_check("embedding", result);
+        }
+        if reranking_ready && !reranking_checked {
+            reranking_checked = settle_consistency_check("reranking", reranking_service.check_consistency().await);
+        }
+        let ready = embedding_ready && reranking_ready && embedding_checked && reranking_checked;
+
+        match (lifecycle.get(), ready) {
+            (LifecycleState::Ready, true) => {}
+            (LifecycleState::Ready, false) => {
+                warn!("Triton failed a readiness check");
+                lifecycle.set(LifecycleState::Degraded);
+            }
+            (_, true) => {
+                lifecycle.set(LifecycleState::Warming);
+                match warm_up(&embedding_service, &reranking_service).await {
+                    Ok(()) => lifecycle.set(LifecycleState::Ready),
+                    Err(e) => warn!("Warmup failed, retrying in {:?}: {}", poll_interval, e),
+                }
+            }
+            (_, false) => {}
+        }
+
+        tokio::time::sleep(poll_interval).await;
+    }
+}
+
+/// Logs a consistency check result and returns whether the check is done:
+/// it passed, or failed without `consistency_check_fatal`. A fatal failure
+/// exits the process; Triton becoming unavailable mid-check retries it on
+/// the next poll.
+fn settle_consistency_check(model: &str, result: Result<(), AppError>) -> bool {
+    match result {
+        Ok(()) => {
+            info!("{} consistency check passed", model);
+            true
+        }
+        Err(AppError::TritonConnection(e) | AppError::NotReady(e)) => {
+            warn!("Could not run {} consistency check, retrying: {}", model, e);
+            false
+        }
+        Err(e) => {
+            error!("{} consistency check failed: {}", model, e);
+            if Settings::get().consistency_check_fatal {
+                error!("Exiting: inconsistent {} tokenizer and model", model);
+                std::process::exit(1);
+            }
+            true
+        }
+    }
+}
+
+/// Sends `warmup_requests` embedding and rerank requests so the first real
+/// requests don't pay for model loading and kernel compilation.
+async fn warm_up(
+    embedding_service: &EmbeddingService,
+    reranking_service: &RerankingService,
+) -> Result<(), AppError> {
+    let settings = Settings::get();
+    let task = settings.enabled_tasks
+        .as_ref()
+        .and_then(|tasks| tasks.first())
+        .map_or("retrieval.query", String::as_str);
+
+    for _ in 0..settings.warmup_requests {
+        embedding_service
+            .create_embeddings(
+                vec![WARMUP_TEXT.to_string()],
+                task,
+                None,
+                EmbeddingOptions { no_cache: true, ..Default::default() },
+            )
+            .await?;
+        reranking_service
+            .rerank_documents(
+                WARMUP_TEXT.to_string(),
+                vec![WARMUP_TEXT.to_string()],
+                RerankOptions::default(),
+            )
+            .await?;
+    }
+
+    info!("Warmup finished ({} requests per model)", settings.warmup_requests);
+    Ok(())
+}
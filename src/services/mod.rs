@@ -1,3 +1,7 @@
 pub mod tokenizer_service;
 pub mod embedding_service;
+pub mod embedding_cache;
 pub mod reranking_service;
+pub mod lifecycle;
+pub mod preprocessing;
+pub mod projection;
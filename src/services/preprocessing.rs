@@ -0,0 +1,36 @@
+use crate::config::Settings;
+
+/// Byte order mark, often left at the start of text copied from files
+const BOM: char = '\u{feff}';
+
+/// Applies the configured text normalization to an embedding input.
+///
+/// The same steps run for every task, so documents indexed and queries
+/// embedded through this service are normalized identically.
+pub fn preprocess_text(text: String) -> String {
+    let settings = Settings::get();
+
+    let mut text = text;
+
+    if settings.preprocess_strip_bom && text.contains(BOM) {
+        text = text.replace(BOM, "");
+    }
+
+    if settings.preprocess_trim_trailing_whitespace {
+        text = text.lines().map(str::trim_end).collect::<Vec<_>>().join("\n");
+    }
+
+    if settings.preprocess_lowercase {
+        text = text.to_lowercase();
+    }
+
+    if settings.preprocess_collapse_whitespace {
+        text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    }
+
+    if settings.preprocess_strip {
+        text = text.trim().to_string();
+    }
+
+    text
+}
@@ -0,0 +1,62 @@
+use std::fs;
+use tracing::info;
+
+use crate::error::AppError;
+
+/// Linear projection applied to model embeddings, e.g. precomputed PCA
+/// components for deployment-specific dimensionality reduction.
+///
+/// The matrix file is JSON: an array of `output_dim` rows, each holding
+/// `model_dim` floats. Every embedding is multiplied by it, so indexed
+/// documents and queries are reduced identically.
+pub struct Projection {
+    components: Vec<Vec<f32>>,
+    input_dim: usize,
+}
+
+impl Projection {
+    pub fn load(path: &str) -> Result<Self, AppError> {
+        let contents = fs::read_to_string(path).map_err(|e| {
+            AppError::Internal(format!("Failed to read projection matrix {}: {}", path, e))
+        })?;
+        let components: Vec<Vec<f32>> = serde_json::from_str(&contents).map_err(|e| {
+            AppError::Internal(format!("Invalid projection matrix {}: {}", path, e))
+        })?;
+
+        let input_dim = components.first().map(Vec::len).unwrap_or(0);
+        if input_dim == 0 {
+            return Err(AppError::Internal(format!("Projection matrix {} is empty", path)));
+        }
+        if let Some(row) = components.iter().position(|row| row.len() != input_dim) {
+            return Err(AppError::Internal(format!(
+                "Projection matrix {} row {} has {} columns, expected {}",
+                path, row, components[row].len(), input_dim
+            )));
+        }
+
+        info!("Loaded projection matrix from {}: {} -> {} dimensions", path, input_dim, components.len());
+        Ok(Self { components, input_dim })
+    }
+
+    pub fn input_dim(&self) -> usize {
+        self.input_dim
+    }
+
+    pub fn output_dim(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn apply(&self, vector: &[f32]) -> Result<Vec<f32>, AppError> {
+        if vector.len() != self.input_dim {
+            return Err(AppError::Inference(format!(
+                "Model returned {}-dimensional embeddings but the projection matrix expects {}",
+                vector.len(), self.input_dim
+            )));
+        }
+
+        Ok(self.components
+            .iter()
+            .map(|row| row.iter().zip(vector).map(|(w, v)| w * v).sum())
+            .collect())
+    }
+}
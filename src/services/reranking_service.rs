@@ -1,24 +1,55 @@
 use crate::error::AppError;
-use crate::models::RerankModel;
-use crate::repositories::triton_client::TritonClient;
-use crate::services::tokenizer_service::TokenizerService;
+use crate::metrics::Metrics;
+use crate::models::{RerankModel, RerankOptions, RerankOutput, ScoreFormat};
+use crate::repositories::RerankingRepository;
+use crate::services::tokenizer_service::{run_blocking, PairSequence, TokenizerService};
 use crate::config::Settings;
-use tracing::info;
+use futures_util::{Stream, StreamExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn, Instrument, Span};
 
 pub struct RerankingService {
-    client: TritonClient,
+    client: Box<dyn RerankingRepository>,
     tokenizer_service: TokenizerService,
+    model_version: RwLock<Option<String>>,
+    /// Last readiness reported by Triton
+    ready: AtomicBool,
+    /// When `ready` was last refreshed, for `health_cache_ttl_secs`
+    ready_checked_at: Mutex<Option<Instant>>,
+    /// Process-wide budget of documents being scored at once
+    document_permits: Option<Arc<Semaphore>>,
+}
+
+/// Permits held by one rerank request, one per document, released on drop
+struct DocumentReservation {
+    _permit: OwnedSemaphorePermit,
+    documents: u64,
+}
+
+impl Drop for DocumentReservation {
+    fn drop(&mut self) {
+        Metrics::get()
+            .rerank_documents_reserved
+            .fetch_sub(self.documents, Ordering::Relaxed);
+    }
 }
 
 impl RerankingService {
-    pub fn new() -> Result<Self, AppError> {
-        let settings = Settings::get();
-        let client = TritonClient::new(settings.reranker_model_name.clone())?;
+    pub fn new(client: Box<dyn RerankingRepository>) -> Result<Self, AppError> {
         let tokenizer_service = TokenizerService::new();
 
         Ok(Self {
             client,
             tokenizer_service,
+            model_version: RwLock::new(None),
+            ready: AtomicBool::new(false),
+            ready_checked_at: Mutex::new(None),
+            document_permits: Settings::get()
+                .max_concurrent_rerank_documents
+                .map(|limit| Arc::new(Semaphore::new(limit))),
         })
     }
 
@@ -26,54 +57,376 @@ impl RerankingService {
         &self,
         query: String,
         documents: Vec<String>,
-        top_n: Option<usize>,
-        return_documents: bool,
-    ) -> Result<Vec<RerankModel>, AppError> {
+        options: RerankOptions,
+    ) -> Result<RerankOutput, AppError> {
+        let RerankOptions { top_n, offset, return_documents, score_format, timeout } = options;
         if documents.is_empty() {
             return Err(AppError::Validation("Documents cannot be empty".to_string()));
         }
+        check_blank_documents(&documents)?;
+        if top_n == Some(0) {
+            return Err(AppError::Validation("top_n must be at least 1".to_string()));
+        }
 
-        info!("Reranking {} documents", documents.len());
+        let offset = offset.unwrap_or(0);
+        if offset >= documents.len() {
+            return Err(AppError::Validation(format!(
+                "offset {} is out of range for {} documents",
+                offset,
+                documents.len()
+            )));
+        }
 
-        let (input_ids, attention_mask) = self.tokenizer_service
-            .tokenize_for_reranking(&query, &documents)?;
+        info!("Reranking {} documents", documents.len());
 
-        let scores = self.client
-            .get_scores(&input_ids, &attention_mask)
-            .await?;
+        let started = Instant::now();
+        let tokenizer_service = self.tokenizer_service;
+        let (documents, sequences) = run_blocking(move || {
+            let sequences = tokenizer_service.tokenize_for_reranking(&query, &documents);
+            (documents, sequences)
+        })
+        .await?;
+        let sequences = sequences?;
+        let tokenize_ms = started.elapsed().as_millis() as u64;
+        info!(tokenize_ms, "Tokenized {} query/document pairs", documents.len());
+        let total_tokens = sequences
+            .iter()
+            .map(|sequence| TokenizerService::token_count(&sequence.encoded))
+            .sum();
 
-        let mut results: Vec<RerankModel> = scores
-            .into_iter()
-            .enumerate()
-            .map(|(index, relevance_score)| {
-                let document = if return_documents {
-                    Some(documents[index].clone())
-                } else {
-                    None
-                };
-                RerankModel {
-                    index,
-                    relevance_score,
-                    document,
-                }
-            })
-            .collect();
+        let _reservation = self.reserve_documents(documents.len()).await?;
+        let max_batch = Settings::get().reranker_client_max_batch.max(1);
+        let mut scores = Vec::with_capacity(sequences.len());
+        for chunk in sequences.chunks(max_batch) {
+            scores.extend(self.score_batch(chunk, timeout).await?);
+        }
 
-        // Sort by relevance score in descending order
-        results.sort_by(|a, b| b.relevance_score.partial_cmp(&a.relevance_score).unwrap());
+        let mut results = build_results(0, scores, &documents, return_documents);
+        results.sort_by(by_relevance);
 
-        // Apply top_n filter if specified
+        // Return the page [offset, offset + top_n) of the ranked results; a
+        // top_n past the end returns the rest of the list
+        results.drain(..offset);
         if let Some(n) = top_n {
             results.truncate(n);
         }
 
+        apply_score_format(&mut results, score_format);
+
         info!("Successfully reranked documents, returning {} results", results.len());
-        Ok(results)
+        Ok(RerankOutput { results, total_tokens })
     }
 
+    /// Scores documents chunk by chunk, yielding each chunk's results as soon
+    /// as Triton returns them. Results are unsorted and keep their original
+    /// indices; `top_n` and softmax need the full set and aren't available.
+    pub async fn rerank_documents_stream(
+        self: Arc<Self>,
+        query: String,
+        documents: Vec<String>,
+        return_documents: bool,
+        score_format: ScoreFormat,
+        timeout: Option<Duration>,
+    ) -> Result<impl Stream<Item = Result<Vec<RerankModel>, AppError>> + Send + 'static, AppError> {
+        if documents.is_empty() {
+            return Err(AppError::Validation("Documents cannot be empty".to_string()));
+        }
+        check_blank_documents(&documents)?;
+        if score_format == ScoreFormat::Softmax {
+            return Err(AppError::Validation(
+                "score_format 'softmax' is not supported when streaming results".to_string(),
+            ));
+        }
+
+        info!("Streaming rerank of {} documents", documents.len());
+
+        let started = Instant::now();
+        let tokenizer_service = self.tokenizer_service;
+        let (documents, sequences) = run_blocking(move || {
+            let sequences = tokenizer_service.tokenize_for_reranking(&query, &documents);
+            (documents, sequences)
+        })
+        .await?;
+        let sequences = sequences?;
+        let tokenize_ms = started.elapsed().as_millis() as u64;
+        info!(tokenize_ms, "Tokenized {} query/document pairs", documents.len());
+
+        let max_batch = Settings::get().reranker_client_max_batch.max(1);
+        let chunks: Vec<(usize, Vec<PairSequence>)> = sequences
+            .chunks(max_batch)
+            .enumerate()
+            .map(|(i, chunk)| (i * max_batch, chunk.to_vec()))
+            .collect();
+        let documents = Arc::new(documents);
+
+        let reservation = self.reserve_documents(documents.len()).await?;
+        // Chunks are scored after the handler returns; keep them in its span
+        let span = Span::current();
+
+        Ok(futures_util::stream::iter(chunks).then(move |(offset, chunk)| {
+            let service = self.clone();
+            let documents = documents.clone();
+            // Captured so the permits are held until the stream is dropped
+            let _reservation = &reservation;
+            async move {
+                let scores = service.score_batch(&chunk, timeout).await?;
+                let mut results = build_results(offset, scores, &documents, return_documents);
+                apply_score_format(&mut results, score_format);
+                Ok(results)
+            }
+            .instrument(span.clone())
+        }))
+    }
+
+    /// Waits for one `max_concurrent_rerank_documents` permit per document.
+    /// Returns `None` when the limit is not configured.
+    async fn reserve_documents(&self, documents: usize) -> Result<Option<DocumentReservation>, AppError> {
+        let Some(permits) = &self.document_permits else {
+            return Ok(None);
+        };
+
+        let limit = Settings::get().max_concurrent_rerank_documents.unwrap_or(0);
+        if documents > limit {
+            return Err(AppError::Validation(format!(
+                "Too many documents: {} provided, max_concurrent_rerank_documents is {}",
+                documents, limit
+            )));
+        }
+
+        let permit = permits.clone()
+            .acquire_many_owned(documents as u32)
+            .await
+            .map_err(|e| AppError::Internal(format!("Rerank document permits closed: {}", e)))?;
+        Metrics::get()
+            .rerank_documents_reserved
+            .fetch_add(documents as u64, Ordering::Relaxed);
+
+        Ok(Some(DocumentReservation {
+            _permit: permit,
+            documents: documents as u64,
+        }))
+    }
+
+    async fn score_batch(&self, sequences: &[PairSequence], timeout: Option<Duration>) -> Result<Vec<f32>, AppError> {
+        let (input_ids, attention_mask) = TokenizerService::pad_batch(sequences.iter().map(|sequence| &sequence.encoded));
+        let padded_length = input_ids.first().map_or(0, Vec::len);
+        Metrics::get()
+            .reranker_sequence_length
+            .observe(padded_length as u64);
+
+        let token_type_ids = Settings::get()
+            .reranker_token_type_ids
+            .then(|| TokenizerService::pad_token_type_ids(sequences, padded_length));
+        let scores = self.client
+            .generate_scores(&input_ids, &attention_mask, token_type_ids.as_deref(), timeout)
+            .await?;
+
+        // Results are matched to documents by position, so a short or long
+        // reply would misattribute scores
+        if scores.len() != sequences.len() {
+            return Err(AppError::Inference(format!(
+                "Triton returned {} scores for {} query/document pairs",
+                scores.len(),
+                sequences.len()
+            )));
+        }
+        Ok(scores)
+    }
+
+    /// Triton model version serving this service, fetched once and cached.
+    pub async fn model_version(&self) -> Option<String> {
+        if !Settings::get().model_version_header {
+            return None;
+        }
+
+        if let Some(version) = self.model_version.read().unwrap().clone() {
+            return Some(version);
+        }
+
+        match self.client.get_model_version().await {
+            Ok(Some(version)) => {
+                *self.model_version.write().unwrap() = Some(version.clone());
+                Some(version)
+            }
+            Ok(None) => {
+                warn!("Failed to fetch model version: Triton reported no model versions");
+                None
+            }
+            Err(e) => {
+                warn!("Failed to fetch model version: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Clears this service's cached values, returning how many were cleared.
+    pub fn clear_caches(&self) -> usize {
+        usize::from(self.model_version.write().unwrap().take().is_some())
+            + usize::from(self.ready_checked_at.lock().unwrap().take().is_some())
+    }
+
+    /// Fails with `NotReady` when this service's Triton model can't serve.
+    pub async fn ensure_ready(&self) -> Result<(), AppError> {
+        if self.is_ready().await.unwrap_or(false) {
+            Ok(())
+        } else {
+            Err(AppError::NotReady(format!(
+                "Reranking model '{}' is not ready, try again later",
+                Settings::get().reranker_model_name
+            )))
+        }
+    }
+
+    /// Scores a known query against two documents end to end, failing when
+    /// Triton rejects the tokenized input or returns an unexpected number of
+    /// scores or non-finite scores.
+    pub async fn check_consistency(&self) -> Result<(), AppError> {
+        let documents = vec![
+            "Machine learning là một nhánh của trí tuệ nhân tạo.".to_string(),
+            "Hôm nay trời đẹp.".to_string(),
+        ];
+        let results = self
+            .rerank_documents("Machine learning là gì?".to_string(), documents, RerankOptions::default())
+            .await?
+            .results;
+
+        if results.len() != 2 || results.iter().any(|result| !result.relevance_score.is_finite()) {
+            return Err(AppError::Internal(format!(
+                "Reranker consistency check expected 2 finite scores, got {:?}; check that the tokenizer and model match",
+                results.iter().map(|result| result.relevance_score).collect::<Vec<_>>()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Checks that the Triton model exposes the output tensor this service reads.
+    pub async fn check_output_tensor(&self) -> Result<(), AppError> {
+        self.client.check_output_name().await
+    }
+
+    /// Model readiness: the background poller's last result when
+    /// `readiness_poll_secs` is set, the last live result while it is younger
+    /// than `health_cache_ttl_secs`, otherwise a live query to Triton.
     pub async fn is_ready(&self) -> Result<bool, AppError> {
-        let live = self.client.is_server_live().await?;
-        let ready = self.client.is_model_ready().await?;
-        Ok(live && ready)
+        let settings = Settings::get();
+        if settings.readiness_poll_secs.is_some() {
+            return Ok(self.ready.load(Ordering::Relaxed));
+        }
+
+        if let Some(ttl) = settings.health_cache_ttl_secs {
+            let fresh = self.ready_checked_at
+                .lock()
+                .unwrap()
+                .is_some_and(|checked_at| checked_at.elapsed() < Duration::from_secs(ttl));
+            if fresh {
+                return Ok(self.ready.load(Ordering::Relaxed));
+            }
+        }
+
+        self.check_ready().await
+    }
+
+    /// Queries Triton for readiness and remembers the result, logging when it
+    /// changes.
+    pub async fn check_ready(&self) -> Result<bool, AppError> {
+        let result = self.client.is_ready().await;
+        let ready = *result.as_ref().unwrap_or(&false);
+        *self.ready_checked_at.lock().unwrap() = Some(Instant::now());
+        if self.ready.swap(ready, Ordering::Relaxed) != ready {
+            info!(
+                "Reranking model '{}' is now {}",
+                Settings::get().reranker_model_name,
+                if ready { "ready" } else { "not ready" }
+            );
+        }
+        result
+    }
+}
+
+/// Rejects empty or whitespace-only documents unless `allow_blank_inputs` is set.
+fn check_blank_documents(documents: &[String]) -> Result<(), AppError> {
+    if Settings::get().allow_blank_inputs {
+        return Ok(());
+    }
+    match documents.iter().position(|document| document.trim().is_empty()) {
+        Some(index) => Err(AppError::Validation(format!(
+            "Document at index {} is empty or whitespace-only",
+            index
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Orders results by relevance score, highest first, breaking ties by
+/// original index so identical inputs always come back in the same order.
+fn by_relevance(a: &RerankModel, b: &RerankModel) -> std::cmp::Ordering {
+    b.relevance_score
+        .total_cmp(&a.relevance_score)
+        .then_with(|| a.index.cmp(&b.index))
+}
+
+/// Pairs scores for the documents starting at `offset` with their indices.
+fn build_results(
+    offset: usize,
+    scores: Vec<f32>,
+    documents: &[String],
+    return_documents: bool,
+) -> Vec<RerankModel> {
+    scores
+        .into_iter()
+        .enumerate()
+        .map(|(i, relevance_score)| {
+            let index = offset + i;
+            let document = if return_documents {
+                Some(documents[index].clone())
+            } else {
+                None
+            };
+            RerankModel {
+                index,
+                relevance_score,
+                document,
+            }
+        })
+        .collect()
+}
+
+/// Converts raw logits in place. Softmax is taken over `results` as given,
+/// i.e. after `top_n` has been applied.
+fn apply_score_format(results: &mut [RerankModel], score_format: ScoreFormat) {
+    match score_format {
+        ScoreFormat::Raw => {}
+        ScoreFormat::Sigmoid => {
+            for result in results.iter_mut() {
+                result.relevance_score = 1.0 / (1.0 + (-result.relevance_score).exp());
+            }
+        }
+        ScoreFormat::Softmax => {
+            let max = results.iter()
+                .map(|r| r.relevance_score)
+                .fold(f32::NEG_INFINITY, f32::max);
+            let sum: f32 = results.iter()
+                .map(|r| (r.relevance_score - max).exp())
+                .sum();
+            for result in results.iter_mut() {
+                result.relevance_score = (result.relevance_score - max).exp() / sum;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tied_scores_keep_original_order() {
+        let documents = ["a", "b", "c", "d"].map(String::from);
+        let mut results = build_results(0, vec![0.5, 0.9, 0.5, 0.5], &documents, false);
+        results.reverse();
+        results.sort_by(by_relevance);
+
+        let order: Vec<usize> = results.iter().map(|result| result.index).collect();
+        assert_eq!(order, [1, 0, 2, 3]);
     }
 }
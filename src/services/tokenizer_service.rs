@@ -1,12 +1,85 @@
 use crate::error::AppError;
-use crate::config::Settings;
-use tokenizers::tokenizer::Tokenizer;
-use std::sync::OnceLock;
-use tracing::{info, error};
+use crate::hf_hub::{self, HF_BASE_URL};
+use crate::config::{QueryOverflow, SeparatorHandling, Settings};
+use crate::models::Truncation;
+use tokenizers::tokenizer::{Tokenizer, TruncationParams, TruncationStrategy};
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tracing::{info, warn, error, Span};
 
-static EMBEDDING_TOKENIZER: OnceLock<Tokenizer> = OnceLock::new();
-static RERANKER_TOKENIZER: OnceLock<Tokenizer> = OnceLock::new();
+/// Unpadded `(input_ids, attention_mask)` for a single sequence.
+pub type EncodedSequence = (Vec<i64>, Vec<i64>);
 
+/// Padded `(input_ids, attention_mask)` pair for a batch of sequences.
+pub type TokenizedBatch = (Vec<Vec<i64>>, Vec<Vec<i64>>);
+
+/// A query/document pair encoding with the segment (token type) of each token
+#[derive(Debug, Clone)]
+pub struct PairSequence {
+    pub encoded: EncodedSequence,
+    pub token_type_ids: Vec<i64>,
+}
+
+/// Upper bound on downloading a `tokenizer.json`, so a stalled Hugging Face
+/// download fails startup instead of hanging it
+const TOKENIZER_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// A tokenizer with the text of its special tokens, replaced as a whole
+/// when tokenizers are reloaded
+struct LoadedTokenizer {
+    tokenizer: Tokenizer,
+    special_tokens: Vec<String>,
+}
+
+impl LoadedTokenizer {
+    fn new(tokenizer: Tokenizer) -> Self {
+        let special_tokens = tokenizer
+            .get_added_tokens_decoder()
+            .into_values()
+            .filter(|token| token.special && !token.content.is_empty())
+            .map(|token| token.content)
+            .collect();
+        Self { tokenizer, special_tokens }
+    }
+}
+
+type TokenizerSlot = RwLock<Option<Arc<LoadedTokenizer>>>;
+
+static EMBEDDING_TOKENIZER: TokenizerSlot = RwLock::new(None);
+static RERANKER_TOKENIZER: TokenizerSlot = RwLock::new(None);
+
+/// The tokenizer currently in `slot`. Callers keep using the one they got
+/// even if a reload swaps it mid-request.
+fn current(slot: &TokenizerSlot, kind: &str) -> Result<Arc<LoadedTokenizer>, AppError> {
+    slot.read()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| AppError::Internal(format!("{} tokenizer not initialized", kind)))
+}
+
+/// Fills `slot` at startup, failing if it was already filled.
+fn install(slot: &TokenizerSlot, kind: &str, tokenizer: Tokenizer) -> Result<(), AppError> {
+    let mut slot = slot.write().unwrap();
+    if slot.is_some() {
+        return Err(AppError::Internal(format!("{} tokenizer already initialized", kind)));
+    }
+    *slot = Some(Arc::new(LoadedTokenizer::new(tokenizer)));
+    Ok(())
+}
+
+/// Vocabulary sizes, including added tokens, after a reload
+#[derive(Debug, Clone, Copy)]
+pub struct TokenizerVocabSizes {
+    pub embedding: usize,
+    pub reranker: usize,
+}
+
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+#[derive(Debug, Clone, Copy)]
 pub struct TokenizerService;
 
 impl TokenizerService {
@@ -14,161 +87,555 @@ impl TokenizerService {
         Self
     }
 
-    pub fn load_embedding_tokenizer() -> Result<(), AppError> {
+    pub async fn load_embedding_tokenizer() -> Result<(), AppError> {
         let settings = Settings::get();
-        
-        let file_path = settings.tokenizer_file.as_ref()
-            .ok_or_else(|| {
-                error!("TOKENIZER_FILE environment variable not set. Please set it to the path of tokenizer.json");
-                AppError::Tokenization("TOKENIZER_FILE not configured".to_string())
-            })?;
-        
-        info!("Loading embedding tokenizer from: {}", file_path);
-        let tokenizer = Tokenizer::from_file(file_path)
-            .map_err(|e| {
-                error!("Failed to load embedding tokenizer from {}: {}", file_path, e);
-                AppError::Tokenization(format!("Failed to load tokenizer: {}", e))
-            })?;
-        
-        EMBEDDING_TOKENIZER.set(tokenizer).map_err(|_| 
-            AppError::Internal("Embedding tokenizer already initialized".to_string())
-        )?;
+        let tokenizer = load_tokenizer(
+            "embedding",
+            settings.tokenizer_file.as_deref(),
+            &settings.tokenizer_path,
+        ).await?;
+
+        install(&EMBEDDING_TOKENIZER, "Embedding", tokenizer)?;
         
         info!("Embedding tokenizer loaded successfully");
         Ok(())
     }
 
-    pub fn load_reranker_tokenizer() -> Result<(), AppError> {
+    pub async fn load_reranker_tokenizer() -> Result<(), AppError> {
         let settings = Settings::get();
-        
-        let file_path = settings.reranker_tokenizer_file.as_ref()
-            .ok_or_else(|| {
-                error!("RERANKER_TOKENIZER_FILE environment variable not set. Please set it to the path of tokenizer.json");
-                AppError::Tokenization("RERANKER_TOKENIZER_FILE not configured".to_string())
-            })?;
-        
-        info!("Loading reranker tokenizer from: {}", file_path);
-        let tokenizer = Tokenizer::from_file(file_path)
-            .map_err(|e| {
-                error!("Failed to load reranker tokenizer from {}: {}", file_path, e);
-                AppError::Tokenization(format!("Failed to load tokenizer: {}", e))
-            })?;
-        
-        RERANKER_TOKENIZER.set(tokenizer).map_err(|_| 
-            AppError::Internal("Reranker tokenizer already initialized".to_string())
-        )?;
+        let tokenizer = load_tokenizer(
+            "reranker",
+            settings.reranker_tokenizer_file.as_deref(),
+            &settings.reranker_tokenizer_path,
+        ).await?;
+
+        install(&RERANKER_TOKENIZER, "Reranker", with_pair_truncation(tokenizer)?)?;
         
         info!("Reranker tokenizer loaded successfully");
         Ok(())
     }
 
+    /// Loads both tokenizers again from the configured files or paths and
+    /// swaps them in. Neither is replaced unless both load; requests already
+    /// tokenizing finish with the old ones.
+    pub async fn reload_tokenizers() -> Result<TokenizerVocabSizes, AppError> {
+        let settings = Settings::get();
+        let embedding = load_tokenizer(
+            "embedding",
+            settings.tokenizer_file.as_deref(),
+            &settings.tokenizer_path,
+        ).await?;
+        let reranker = with_pair_truncation(load_tokenizer(
+            "reranker",
+            settings.reranker_tokenizer_file.as_deref(),
+            &settings.reranker_tokenizer_path,
+        ).await?)?;
+
+        let sizes = TokenizerVocabSizes {
+            embedding: embedding.get_vocab_size(true),
+            reranker: reranker.get_vocab_size(true),
+        };
+        *EMBEDDING_TOKENIZER.write().unwrap() = Some(Arc::new(LoadedTokenizer::new(embedding)));
+        *RERANKER_TOKENIZER.write().unwrap() = Some(Arc::new(LoadedTokenizer::new(reranker)));
+
+        info!("Reloaded tokenizers: embedding vocab {}, reranker vocab {}", sizes.embedding, sizes.reranker);
+        Ok(sizes)
+    }
+
+    /// Tokenizes and truncates each text without padding, so callers can
+    /// group the sequences into batches before padding them.
+    ///
+    /// Each input gets its own result, so a failure on one text does not
+    /// discard the others; callers decide whether it fails the request.
+    /// Over-length inputs are an error under `Truncation::None`.
     pub fn tokenize_for_embedding(
         &self,
         texts: &[String],
-    ) -> Result<(Vec<Vec<i64>>, Vec<Vec<i64>>), AppError> {
-        let tokenizer = EMBEDDING_TOKENIZER.get()
-            .ok_or_else(|| AppError::Internal("Embedding tokenizer not initialized".to_string()))?;
+        truncation: Truncation,
+    ) -> Result<Vec<Result<EncodedSequence, AppError>>, AppError> {
+        let loaded = current(&EMBEDDING_TOKENIZER, "Embedding")?;
+        let tokenizer = &loaded.tokenizer;
 
         let settings = Settings::get();
         let max_length = settings.max_sequence_length;
 
-        let mut all_input_ids = Vec::new();
-        let mut all_attention_masks = Vec::new();
+        let mut sequences = Vec::with_capacity(texts.len());
 
-        // First pass: tokenize and truncate if needed
         for text in texts {
-            let encoding = tokenizer
-                .encode(text.clone(), true)
-                .map_err(|e| AppError::Tokenization(e.to_string()))?;
+            let encoding = match tokenizer.encode(text.clone(), true) {
+                Ok(encoding) => encoding,
+                Err(e) => {
+                    sequences.push(Err(AppError::Tokenization(e.to_string())));
+                    continue;
+                }
+            };
 
             let mut input_ids: Vec<i64> = encoding.get_ids().iter().map(|&x| x as i64).collect();
             let mut attention_mask: Vec<i64> = encoding.get_attention_mask().iter().map(|&x| x as i64).collect();
 
-            // Truncate if exceeds max_length
             if input_ids.len() > max_length {
-                input_ids.truncate(max_length);
-                attention_mask.truncate(max_length);
+                match truncation {
+                    Truncation::Right => {
+                        input_ids.truncate(max_length);
+                        attention_mask.truncate(max_length);
+                    }
+                    Truncation::Left => {
+                        let excess = leading_tokens_to_drop(encoding.get_special_tokens_mask(), max_length);
+                        input_ids.drain(excess.clone());
+                        attention_mask.drain(excess);
+                        input_ids.truncate(max_length);
+                        attention_mask.truncate(max_length);
+                    }
+                    Truncation::None => {
+                        sequences.push(Err(AppError::Validation(format!(
+                            "Input is {} tokens, more than max_sequence_length {}; shorten it or set truncation to 'right' or 'left'",
+                            input_ids.len(),
+                            max_length
+                        ))));
+                        continue;
+                    }
+                }
             }
 
-            all_input_ids.push(input_ids);
-            all_attention_masks.push(attention_mask);
+            sequences.push(Ok((input_ids, attention_mask)));
         }
 
-        // Find the longest sequence in this batch
-        let batch_max_length = all_input_ids.iter()
-            .map(|ids| ids.len())
-            .max()
-            .unwrap_or(0);
+        Ok(sequences)
+    }
+
+    /// Turns client-supplied token ids into sequences attending to every
+    /// token, applying `truncation` like `tokenize_for_embedding`. Ids are
+    /// used as given, so they must already include any special tokens.
+    ///
+    /// Each input gets its own result; empty inputs and ids outside the
+    /// embedding tokenizer's vocabulary are errors.
+    pub fn encode_token_ids(
+        &self,
+        token_ids: Vec<Vec<u32>>,
+        truncation: Truncation,
+    ) -> Result<Vec<Result<EncodedSequence, AppError>>, AppError> {
+        let vocab_size = current(&EMBEDDING_TOKENIZER, "Embedding")?.tokenizer.get_vocab_size(true);
+        let max_length = Settings::get().max_sequence_length;
 
-        info!("Batch padding: longest sequence = {} tokens (max allowed = {})", 
-              batch_max_length, max_length);
+        let sequences = token_ids
+            .into_iter()
+            .map(|ids| {
+                if ids.is_empty() {
+                    return Err(AppError::Validation("Token id input is empty".to_string()));
+                }
+                if let Some(id) = ids.iter().find(|&&id| id as usize >= vocab_size) {
+                    return Err(AppError::Validation(format!(
+                        "Token id {} is out of range for the embedding tokenizer's vocabulary of {} tokens",
+                        id, vocab_size
+                    )));
+                }
 
-        // Second pass: pad all sequences to the batch max length
-        for (input_ids, attention_mask) in all_input_ids.iter_mut().zip(all_attention_masks.iter_mut()) {
+                let mut input_ids: Vec<i64> = ids.into_iter().map(i64::from).collect();
+                if input_ids.len() > max_length {
+                    match truncation {
+                        Truncation::Right => input_ids.truncate(max_length),
+                        // Without a special tokens mask, only the last tokens are kept
+                        Truncation::Left => {
+                            input_ids.drain(..input_ids.len() - max_length);
+                        }
+                        Truncation::None => {
+                            return Err(AppError::Validation(format!(
+                                "Input is {} tokens, more than max_sequence_length {}; shorten it or set truncation to 'right' or 'left'",
+                                input_ids.len(),
+                                max_length
+                            )));
+                        }
+                    }
+                }
+
+                let attention_mask = vec![1; input_ids.len()];
+                Ok((input_ids, attention_mask))
+            })
+            .collect();
+
+        Ok(sequences)
+    }
+
+    /// Number of real (attended) tokens in a sequence, excluding padding.
+    pub fn token_count((_, attention_mask): &EncodedSequence) -> usize {
+        attention_mask.iter().filter(|&&mask| mask != 0).count()
+    }
+
+    /// Pads a group of encoded sequences to the longest one in the group,
+    /// or to exactly `fixed_sequence_length` when configured (truncating
+    /// longer sequences) so every Triton call sees the same shape.
+    pub fn pad_batch<'a>(
+        sequences: impl IntoIterator<Item = &'a EncodedSequence, IntoIter: ExactSizeIterator + Clone>,
+    ) -> TokenizedBatch {
+        let settings = Settings::get();
+        let sequences = sequences.into_iter();
+
+        let batch_max_length = match settings.fixed_sequence_length {
+            Some(fixed) => fixed,
+            // Find the longest sequence in this batch
+            None => sequences.clone()
+                .map(|(ids, _)| ids.len())
+                .max()
+                .unwrap_or(0),
+        };
+
+        info!("Batch padding: {} sequences, padded length = {} tokens", 
+              sequences.len(), batch_max_length);
+
+        let mut all_input_ids = Vec::with_capacity(sequences.len());
+        let mut all_attention_masks = Vec::with_capacity(sequences.len());
+
+        for (input_ids, attention_mask) in sequences {
+            let mut input_ids = input_ids.clone();
+            let mut attention_mask = attention_mask.clone();
+            input_ids.truncate(batch_max_length);
+            attention_mask.truncate(batch_max_length);
             let padding = batch_max_length - input_ids.len();
             if padding > 0 {
                 input_ids.extend(vec![0; padding]);
                 attention_mask.extend(vec![0; padding]);
             }
+            all_input_ids.push(input_ids);
+            all_attention_masks.push(attention_mask);
         }
 
-        Ok((all_input_ids, all_attention_masks))
+        (all_input_ids, all_attention_masks)
+    }
+
+    /// Pads (or truncates) token type ids to `length` with segment 0, to
+    /// match a batch padded by `pad_batch`.
+    pub fn pad_token_type_ids(sequences: &[PairSequence], length: usize) -> Vec<Vec<i64>> {
+        sequences
+            .iter()
+            .map(|sequence| {
+                let mut type_ids = sequence.token_type_ids.clone();
+                type_ids.resize(length, 0);
+                type_ids
+            })
+            .collect()
     }
 
     pub fn tokenize_for_reranking(
         &self,
         query: &str,
         documents: &[String],
-    ) -> Result<(Vec<Vec<i64>>, Vec<Vec<i64>>), AppError> {
-        let tokenizer = RERANKER_TOKENIZER.get()
-            .ok_or_else(|| AppError::Internal("Reranker tokenizer not initialized".to_string()))?;
+    ) -> Result<Vec<PairSequence>, AppError> {
+        let loaded = current(&RERANKER_TOKENIZER, "Reranker")?;
+        let tokenizer = &loaded.tokenizer;
 
         let settings = Settings::get();
-        let max_length = settings.reranker_max_sequence_length;
 
-        let mut all_input_ids = Vec::new();
-        let mut all_attention_masks = Vec::new();
+        let mut sequences = Vec::with_capacity(documents.len());
+        let query = handle_special_tokens(&loaded, query, "query");
+        let query = match settings.max_query_tokens {
+            Some(max_tokens) => limit_query_tokens(tokenizer, query, max_tokens)?,
+            None => query,
+        };
 
-        // First pass: tokenize and truncate if needed
-        for doc in documents {
-            // Combine query and document
-            let combined = format!("{} [SEP] {}", query, doc);
-            
-            let encoding = tokenizer
-                .encode(combined, true)
-                .map_err(|e| AppError::Tokenization(e.to_string()))?;
+        for (index, doc) in documents.iter().enumerate() {
+            let doc = handle_special_tokens(&loaded, doc, &format!("document {}", index));
 
-            let mut input_ids: Vec<i64> = encoding.get_ids().iter().map(|&x| x as i64).collect();
-            let mut attention_mask: Vec<i64> = encoding.get_attention_mask().iter().map(|&x| x as i64).collect();
+            // Pair encoding lets the tokenizer place its own separator tokens;
+            // a configured separator joins both into a single sequence instead
+            let encoding = match &settings.reranker_separator {
+                Some(separator) => tokenizer.encode(format!("{}{}{}", query, separator, doc), true),
+                None => tokenizer.encode((query.as_ref(), doc.as_ref()), true),
+            }
+            .map_err(|e| AppError::Tokenization(e.to_string()))?;
 
-            // Truncate if exceeds max_length
-            if input_ids.len() > max_length {
-                input_ids.truncate(max_length);
-                attention_mask.truncate(max_length);
+            // Already within reranker_max_sequence_length; see `with_pair_truncation`
+            let input_ids: Vec<i64> = encoding.get_ids().iter().map(|&x| x as i64).collect();
+            let attention_mask: Vec<i64> = encoding.get_attention_mask().iter().map(|&x| x as i64).collect();
+            let token_type_ids: Vec<i64> = encoding.get_type_ids().iter().map(|&x| x as i64).collect();
+
+            sequences.push(PairSequence {
+                encoded: (input_ids, attention_mask),
+                token_type_ids,
+            });
+        }
+
+        Ok(sequences)
+    }
+}
+
+/// Runs CPU-bound tokenization on Tokio's blocking pool so long inputs don't
+/// stall the async workers, keeping the caller's tracing span.
+pub async fn run_blocking<T, F>(tokenize: F) -> Result<T, AppError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let span = Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(tokenize))
+        .await
+        .map_err(|e| AppError::Internal(format!("Tokenization task failed: {}", e)))
+}
+
+/// Positions to remove for left truncation to `max_length`: the tokens right
+/// after the leading special tokens (e.g. `[CLS]`), so those and the trailing
+/// ones (e.g. `[SEP]`) are kept.
+fn leading_tokens_to_drop(special_tokens_mask: &[u32], max_length: usize) -> Range<usize> {
+    let len = special_tokens_mask.len();
+    let prefix = special_tokens_mask.iter().take_while(|&&special| special == 1).count();
+    let suffix = special_tokens_mask[prefix..].iter().rev().take_while(|&&special| special == 1).count();
+    let excess = len.saturating_sub(max_length);
+    prefix..(prefix + excess).min(len - suffix)
+}
+
+/// Applies `reranker_separator_handling` to literal special tokens in `text`,
+/// warning when any are found.
+fn handle_special_tokens<'a>(tokenizer: &LoadedTokenizer, text: &'a str, label: &str) -> Cow<'a, str> {
+    let found: Vec<&String> = tokenizer.special_tokens.iter().filter(|token| text.contains(token.as_str())).collect();
+    if found.is_empty() {
+        return Cow::Borrowed(text);
+    }
+
+    let handling = Settings::get().reranker_separator_handling;
+    warn!("Rerank {} contains special token text {:?} ({:?})", label, found, handling);
+    if handling == SeparatorHandling::Keep {
+        return Cow::Borrowed(text);
+    }
+
+    if handling == SeparatorHandling::Strip {
+        return Cow::Owned(strip_special_tokens(text, &tokenizer.special_tokens));
+    }
+
+    let mut text = text.to_string();
+    for token in found {
+        let mut chars = token.chars();
+        let first = chars.next().unwrap_or_default();
+        let escaped = format!("{}{}{}", first, ZERO_WIDTH_SPACE, chars.as_str());
+        text = text.replace(token.as_str(), &escaped);
+    }
+    Cow::Owned(text)
+}
+
+/// Removes every special token string from `text`. Removing one can join its
+/// neighbours into another (`<</s>/s>`), so this repeats until none is left.
+fn strip_special_tokens(text: &str, special_tokens: &[String]) -> String {
+    let mut text = text.to_string();
+    while let Some(token) = special_tokens.iter().find(|token| text.contains(token.as_str())) {
+        text = text.replace(token.as_str(), "");
+    }
+    text
+}
+
+/// Has the reranker tokenizer truncate to `reranker_max_sequence_length`
+/// itself, trimming the longer of query and document first and keeping the
+/// special tokens, so a pair never loses its closing separator.
+fn with_pair_truncation(mut tokenizer: Tokenizer) -> Result<Tokenizer, AppError> {
+    tokenizer
+        .with_truncation(Some(TruncationParams {
+            max_length: Settings::get().reranker_max_sequence_length,
+            strategy: TruncationStrategy::LongestFirst,
+            ..TruncationParams::default()
+        }))
+        .map_err(|e| AppError::Internal(format!("Invalid reranker truncation settings: {}", e)))?;
+    Ok(tokenizer)
+}
+
+/// Enforces `max_query_tokens` on a rerank query, truncating it at a token
+/// boundary or rejecting it according to `query_overflow`.
+fn limit_query_tokens<'a>(tokenizer: &Tokenizer, query: Cow<'a, str>, max_tokens: usize) -> Result<Cow<'a, str>, AppError> {
+    let encoding = tokenizer
+        .encode(query.as_ref(), false)
+        .map_err(|e| AppError::Tokenization(e.to_string()))?;
+    let token_count = encoding.get_ids().len();
+    if token_count <= max_tokens {
+        return Ok(query);
+    }
+
+    if Settings::get().query_overflow == QueryOverflow::Reject {
+        return Err(AppError::Validation(format!(
+            "Query has {} tokens, exceeding max_query_tokens = {}",
+            token_count, max_tokens
+        )));
+    }
+
+    let end = match max_tokens.checked_sub(1) {
+        Some(last) => encoding.get_offsets()[last].1,
+        None => 0,
+    };
+    warn!("Truncating rerank query from {} to {} tokens", token_count, max_tokens);
+    Ok(Cow::Owned(query.get(..end).unwrap_or(&query).to_string()))
+}
+
+/// Loads a tokenizer from `file` when set. Otherwise `path` is used: a local
+/// `tokenizer.json` (or a directory containing one) if it exists, else a
+/// Hugging Face repo id whose `tokenizer.json` is downloaded.
+async fn load_tokenizer(kind: &str, file: Option<&str>, path: &str) -> Result<Tokenizer, AppError> {
+    let local = match file {
+        Some(file) => Some(PathBuf::from(file)),
+        None => {
+            let path = Path::new(path);
+            if path.is_dir() {
+                Some(path.join("tokenizer.json"))
+            } else if path.is_file() {
+                Some(path.to_path_buf())
+            } else {
+                None
+            }
+        }
+    };
+
+    if let Some(file_path) = local {
+        info!("Loading {} tokenizer from: {}", kind, file_path.display());
+        return Tokenizer::from_file(&file_path).map_err(|e| {
+            error!("Failed to load {} tokenizer from {}: {}", kind, file_path.display(), e);
+            AppError::Tokenization(format!("Failed to load tokenizer: {}", e))
+        });
+    }
+
+    let cache_dir = &Settings::get().tokenizer_cache_dir;
+    let cached = (!cache_dir.is_empty()).then(|| Path::new(cache_dir).join(path).join("tokenizer.json"));
+    if let Some(cached) = cached.as_ref().filter(|cached| cached.is_file()) {
+        match Tokenizer::from_file(cached) {
+            Ok(tokenizer) => {
+                info!("Loaded {} tokenizer from cache: {}", kind, cached.display());
+                return Ok(tokenizer);
             }
+            Err(e) => warn!("Ignoring unreadable cached {} tokenizer {}: {}", kind, cached.display(), e),
+        }
+    }
 
-            all_input_ids.push(input_ids);
-            all_attention_masks.push(attention_mask);
+    let url = format!("{}/{}/resolve/main/tokenizer.json", HF_BASE_URL, path);
+    info!("No local {} tokenizer file configured, downloading from: {}", kind, url);
+
+    let client = hf_hub::client_builder()
+        .map_err(|e| AppError::Tokenization(format!("HF_TOKEN is not a valid header value: {}", e)))?
+        .timeout(TOKENIZER_DOWNLOAD_TIMEOUT)
+        .build()
+        .map_err(|e| AppError::Tokenization(format!("Failed to create HTTP client: {}", e)))?;
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| {
+            error!("Failed to download {} tokenizer from {}: {}", kind, url, e);
+            AppError::Tokenization(format!("Failed to download tokenizer from {}: {}", url, e))
+        })?;
+    let bytes = response.bytes().await.map_err(|e| {
+        AppError::Tokenization(format!("Failed to download tokenizer from {}: {}", url, e))
+    })?;
+
+    let tokenizer = Tokenizer::from_bytes(&bytes).map_err(|e| {
+        error!("Failed to parse {} tokenizer downloaded from {}: {}", kind, url, e);
+        AppError::Tokenization(format!("Failed to load tokenizer: {}", e))
+    })?;
+
+    // A failed cache write only costs a download on the next start
+    if let Some(cached) = cached {
+        match write_cache_file(&cached, &bytes) {
+            Ok(()) => info!("Cached {} tokenizer at {}", kind, cached.display()),
+            Err(e) => warn!("Failed to cache {} tokenizer at {}: {}", kind, cached.display(), e),
         }
+    }
 
-        // Find the longest sequence in this batch
-        let batch_max_length = all_input_ids.iter()
-            .map(|ids| ids.len())
-            .max()
-            .unwrap_or(0);
+    Ok(tokenizer)
+}
 
-        info!("Reranking batch padding: longest sequence = {} tokens (max allowed = {})", 
-              batch_max_length, max_length);
+/// Writes via a temporary file and rename, so a crash mid-write never
+/// leaves a truncated tokenizer in the cache.
+fn write_cache_file(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = path.with_extension("json.part");
+    std::fs::write(&partial, bytes)?;
+    std::fs::rename(&partial, path)
+}
 
-        // Second pass: pad all sequences to the batch max length
-        for (input_ids, attention_mask) in all_input_ids.iter_mut().zip(all_attention_masks.iter_mut()) {
-            let padding = batch_max_length - input_ids.len();
-            if padding > 0 {
-                input_ids.extend(vec![0; padding]);
-                attention_mask.extend(vec![0; padding]);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Instant;
+
+    #[test]
+    fn stripping_does_not_reassemble_special_tokens() {
+        let special_tokens = vec!["</s>".to_string(), "<s>".to_string()];
+        assert_eq!(strip_special_tokens("a <</s>/s> b", &special_tokens), "a  b");
+        assert_eq!(strip_special_tokens("<<s>s>query", &special_tokens), "query");
+    }
+
+    /// Word-level tokenizer over `word0`..`word999`, standing in for a real
+    /// `tokenizer.json` so timing tests need no download.
+    fn fixture_tokenizer() -> Tokenizer {
+        let mut vocab: serde_json::Map<String, serde_json::Value> =
+            (0..1000).map(|i| (format!("word{}", i), serde_json::json!(i))).collect();
+        vocab.insert("[UNK]".to_string(), serde_json::json!(1000));
+        let config = serde_json::json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [],
+            "normalizer": null,
+            "pre_tokenizer": { "type": "Whitespace" },
+            "post_processor": null,
+            "decoder": null,
+            "model": { "type": "WordLevel", "vocab": vocab, "unk_token": "[UNK]" }
+        });
+        Tokenizer::from_str(&config.to_string()).unwrap()
+    }
+
+    /// Tokenizes `documents` concurrently, inline on the async workers or
+    /// through `run_blocking`, while a task sleeps 1ms in a loop. Returns the
+    /// total time and the longest the sleeping task waited to wake up.
+    async fn tokenize_while_ticking(tokenizer: Arc<Tokenizer>, documents: Arc<Vec<String>>, offload: bool) -> (Duration, Duration) {
+        let done = Arc::new(AtomicBool::new(false));
+        let ticker = tokio::spawn({
+            let done = done.clone();
+            async move {
+                let mut worst = Duration::ZERO;
+                while !done.load(Ordering::Relaxed) {
+                    let started = Instant::now();
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                    worst = worst.max(started.elapsed());
+                }
+                worst
             }
+        });
+
+        let started = Instant::now();
+        let tasks: Vec<_> = (0..documents.len())
+            .map(|i| {
+                let tokenizer = tokenizer.clone();
+                let documents = documents.clone();
+                tokio::spawn(async move {
+                    let encode = move || tokenizer.encode(documents[i].as_str(), true).unwrap().len();
+                    if offload {
+                        run_blocking(encode).await.unwrap()
+                    } else {
+                        encode()
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            assert!(task.await.unwrap() > 0);
         }
+        let total = started.elapsed();
+
+        done.store(true, Ordering::Relaxed);
+        (total, ticker.await.unwrap())
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture blocking_tokenization`.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[ignore = "timing benchmark"]
+    async fn blocking_tokenization_keeps_the_runtime_responsive() {
+        let tokenizer = Arc::new(fixture_tokenizer());
+        let document: String = (0..200_000).map(|i| format!("word{} ", i % 1000)).collect();
+        let documents = Arc::new(vec![document; 8]);
+
+        let (inline_total, inline_stall) = tokenize_while_ticking(tokenizer.clone(), documents.clone(), false).await;
+        let (blocking_total, blocking_stall) = tokenize_while_ticking(tokenizer, documents, true).await;
 
-        Ok((all_input_ids, all_attention_masks))
+        println!(
+            "8 documents of 200000 words on 2 workers: inline {:?} (longest 1ms sleep {:?}), run_blocking {:?} (longest 1ms sleep {:?})",
+            inline_total, inline_stall, blocking_total, blocking_stall
+        );
     }
 }